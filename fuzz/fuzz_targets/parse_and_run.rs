@@ -0,0 +1,30 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rz80;
+
+use rz80::Z80;
+use rz80::ops::opcodes::BigReg;
+use rz80::ops::parser::parse_op;
+
+// The first bytes seed the register file, the whole input is loaded as the
+// program at address 0 and then executed for a bounded number of T-states.
+fuzz_target!(|data: &[u8]| {
+    parse_op(&mut data.iter().cloned());
+
+    let mut cpu = Z80::new();
+    for (address, byte) in data.iter().take(65536).enumerate() {
+        cpu.set_mem(address as u16, *byte);
+    }
+
+    let mut words = data.chunks(2).map(|word| {
+        word.iter().fold(0u16, |acc, byte| (acc << 8) | *byte as u16)
+    });
+    let big_regs = [BigReg::AF, BigReg::BC, BigReg::DE, BigReg::HL, BigReg::IX, BigReg::IY, BigReg::SP];
+    for reg in big_regs.iter() {
+        if let Some(word) = words.next() {
+            cpu.set_big_reg(*reg, word);
+        }
+    }
+
+    cpu.run_for_cycles(100000);
+});
@@ -0,0 +1,48 @@
+/// The memory the CPU reads and writes through its address and data buses.
+pub trait MemoryBus {
+    /// Reads the byte at `address`.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Writes `value` to `address`.
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Reads an opcode byte at `address` during an M1 (opcode fetch) cycle.
+    fn fetch_opcode(&mut self, address: u16) -> u8 {
+        self.read(address)
+    }
+}
+
+/// Flat 64 KiB of RAM, the default memory of a `Z80`.
+pub type Memory = [u8; 65536];
+
+impl MemoryBus for Memory {
+    fn read(&mut self, address: u16) -> u8 {
+        self[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self[address as usize] = value;
+    }
+}
+
+/// The devices the CPU talks to through IN and OUT instructions. Ports are
+/// the full 16-bit address the CPU puts on the bus, with the upper byte taken
+/// from A or B depending on the instruction.
+pub trait IoBus {
+    /// Reads a byte from `port`.
+    fn input(&mut self, port: u16) -> u8;
+
+    /// Writes `value` to `port`.
+    fn output(&mut self, port: u16, value: u8);
+}
+
+/// An empty I/O bus: writes are ignored and reads see a floating bus (0xFF).
+pub struct NullIo;
+
+impl IoBus for NullIo {
+    fn input(&mut self, _port: u16) -> u8 {
+        0xFF
+    }
+
+    fn output(&mut self, _port: u16, _value: u8) {}
+}
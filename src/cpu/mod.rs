@@ -1,13 +1,20 @@
+pub mod bus;
 mod tests;
 
 use ops::opcodes::Opcode;
 use ops::opcodes::Reg;
 use ops::opcodes::BigReg;
+use ops::opcodes::Displacement;
+use ops::opcodes::Bit;
+use ops::opcodes::Condition;
+use ops::parser::parse_op;
+use self::bus::IoBus;
+use self::bus::Memory;
+use self::bus::MemoryBus;
+use self::bus::NullIo;
 use std::ops::Index;
 use std::ops::IndexMut;
 
-type Memory = [u8; 65536];
-
 impl Index<Reg> for [u8] {
     type Output = u8;
 
@@ -22,7 +29,19 @@ impl IndexMut<Reg> for [u8] {
     }
 }
 
-struct Z80 {
+/// The register contents a CPU comes up with when power is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerOn {
+    /// Every register cleared.
+    Zeroed,
+    /// AF and SP read 0xFFFF, as on most real chips, the rest cleared.
+    Typical,
+    /// Registers and memory filled with pseudo-random values from the seed.
+    Randomized(u32),
+}
+
+/// A Zilog Z80 CPU together with the memory and I/O devices it addresses.
+pub struct Z80<M: MemoryBus = Memory, I: IoBus = NullIo> {
     regs: [u8; 16],
 
     i: u8,
@@ -31,58 +50,631 @@ struct Z80 {
     iy: u16,
     sp: u16,
     pc: u16,
+    // Internal WZ register, only observable through BIT n,(HL)
+    memptr: u16,
 
-    mem: Memory,
+    mem: M,
+    io: I,
 
     iff1: bool,
     iff2: bool,
+    im: u8,
+    // Data bus bytes supplied while INT is asserted
+    int: Option<Vec<u8>>,
+    nmi: bool,
+    ei_delay: bool,
+    after_ld_a_ir: bool,
+    halted: bool,
+    // Opcode at PC already fetched by the M1 cycle following an unused prefix
+    prefetched: Option<u8>,
+
+    cycles: u64,
+}
+
+impl Default for Z80 {
+    fn default() -> Z80 {
+        Z80::new()
+    }
 }
 
 impl Z80 {
-    fn new() -> Z80 {
+    /// Creates a CPU with every register, flag and memory cell set to zero.
+    pub fn new() -> Z80 {
+        Z80::with_memory([0; 65536])
+    }
+}
+
+impl<M: MemoryBus> Z80<M> {
+    /// Creates a CPU with zeroed registers running on top of `mem`, with no
+    /// I/O devices attached.
+    pub fn with_memory(mem: M) -> Z80<M> {
+        Z80::with_bus(mem, NullIo)
+    }
+}
+
+impl<M: MemoryBus, I: IoBus> Z80<M, I> {
+    /// Creates a CPU with zeroed registers attached to `mem` and `io`.
+    pub fn with_bus(mem: M, io: I) -> Z80<M, I> {
         Z80 {
             regs: [0; 16],
-            i: 0, r: 0, ix: 0, iy: 0, sp: 0, pc:0,
-            mem: [0;65536],
-            iff1: false, iff2: false,
+            i: 0, r: 0, ix: 0, iy: 0, sp: 0, pc:0, memptr: 0,
+            mem,
+            io,
+            iff1: false, iff2: false, im: 0,
+            int: None, nmi: false, ei_delay: false, after_ld_a_ir: false,
+            halted: false, prefetched: None,
+            cycles: 0,
+        }
+    }
+
+    /// Pulls /RESET: clears PC, I and R, disables interrupts and selects
+    /// interrupt mode 0. Every other register keeps its value.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.i = 0;
+        self.r = 0;
+        self.iff1 = false;
+        self.iff2 = false;
+        self.im = 0;
+        self.nmi = false;
+        self.ei_delay = false;
+        self.after_ld_a_ir = false;
+        self.halted = false;
+        self.prefetched = None;
+    }
+
+    /// Simulates applying power: loads the registers as described by `state`
+    /// and then resets the CPU.
+    pub fn power_on(&mut self, state: PowerOn) {
+        match state {
+            PowerOn::Zeroed => {
+                self.regs = [0; 16];
+                self.ix = 0;
+                self.iy = 0;
+                self.sp = 0;
+                self.memptr = 0;
+            },
+            PowerOn::Typical => {
+                self.regs = [0; 16];
+                self.set_reg_pair(Reg::A, Reg::F, 0xFFFF);
+                self.ix = 0;
+                self.iy = 0;
+                self.sp = 0xFFFF;
+                self.memptr = 0;
+            },
+            PowerOn::Randomized(seed) => {
+                // xorshift never leaves a zero state, so avoid starting there
+                let mut state = if seed == 0 { 0x9E3779B9 } else { seed };
+                for reg in self.regs.iter_mut() {
+                    *reg = xorshift(&mut state) as u8;
+                }
+                self.ix = xorshift(&mut state) as u16;
+                self.iy = xorshift(&mut state) as u16;
+                self.sp = xorshift(&mut state) as u16;
+                self.memptr = xorshift(&mut state) as u16;
+                for address in 0..=0xFFFF {
+                    self.mem.write(address, xorshift(&mut state) as u8);
+                }
+            },
         }
+        self.reset();
     }
 
-    fn set_carry(&mut self, value: bool) {
+    /// Returns the value of an 8-bit register, including the alternate set.
+    pub fn get_reg(&self, reg: Reg) -> u8 {
+        self.regs[reg]
+    }
+
+    /// Sets the value of an 8-bit register, including the alternate set.
+    pub fn set_reg(&mut self, reg: Reg, value: u8) {
+        self.regs[reg] = value;
+    }
+
+    /// Returns the interrupt vector register.
+    pub fn get_i(&self) -> u8 { self.i }
+
+    /// Sets the interrupt vector register.
+    pub fn set_i(&mut self, value: u8) { self.i = value; }
+
+    /// Returns the memory refresh register.
+    pub fn get_r(&self) -> u8 { self.r }
+
+    /// Sets the memory refresh register.
+    pub fn set_r(&mut self, value: u8) { self.r = value; }
+
+    /// Returns the IX index register.
+    pub fn get_ix(&self) -> u16 { self.ix }
+
+    /// Sets the IX index register.
+    pub fn set_ix(&mut self, value: u16) { self.ix = value; }
+
+    /// Returns the IY index register.
+    pub fn get_iy(&self) -> u16 { self.iy }
+
+    /// Sets the IY index register.
+    pub fn set_iy(&mut self, value: u16) { self.iy = value; }
+
+    /// Returns the stack pointer.
+    pub fn get_sp(&self) -> u16 { self.sp }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, value: u16) { self.sp = value; }
+
+    /// Returns the program counter.
+    pub fn get_pc(&self) -> u16 { self.pc }
+
+    /// Sets the program counter.
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+        self.prefetched = None;
+    }
+
+    /// Returns the first interrupt enable flip-flop.
+    pub fn get_iff1(&self) -> bool { self.iff1 }
+
+    /// Sets the first interrupt enable flip-flop.
+    pub fn set_iff1(&mut self, value: bool) { self.iff1 = value; }
+
+    /// Returns the second interrupt enable flip-flop.
+    pub fn get_iff2(&self) -> bool { self.iff2 }
+
+    /// Sets the second interrupt enable flip-flop.
+    pub fn set_iff2(&mut self, value: bool) { self.iff2 = value; }
+
+    /// Returns the interrupt mode, 0, 1 or 2.
+    pub fn get_im(&self) -> u8 { self.im }
+
+    /// Sets the interrupt mode, 0, 1 or 2.
+    pub fn set_im(&mut self, value: u8) { self.im = value; }
+
+    /// Asserts the INT line. `data` is the byte the interrupting device puts
+    /// on the data bus when the interrupt is acknowledged.
+    pub fn assert_int(&mut self, data: u8) { self.int = Some(vec![data]); }
+
+    /// Asserts the INT line with a whole instruction on the data bus, such as
+    /// a three byte CALL, for devices relying on interrupt mode 0.
+    pub fn assert_int_instruction(&mut self, data: &[u8]) { self.int = Some(data.to_vec()); }
+
+    /// Releases the INT line.
+    pub fn deassert_int(&mut self) { self.int = None; }
+
+    /// Signals a non-maskable interrupt, accepted before the next instruction.
+    pub fn pulse_nmi(&mut self) { self.nmi = true; }
+
+    /// Returns whether the CPU is halted, as signalled by its HALT line.
+    pub fn get_halted(&self) -> bool { self.halted }
+
+    /// Returns the number of T-states executed since the CPU was created.
+    pub fn get_cycles(&self) -> u64 { self.cycles }
+
+    /// Returns the memory bus the CPU is attached to.
+    pub fn get_memory(&self) -> &M { &self.mem }
+
+    /// Returns the memory bus the CPU is attached to, mutably.
+    pub fn get_memory_mut(&mut self) -> &mut M { &mut self.mem }
+
+    /// Returns the I/O bus the CPU is attached to.
+    pub fn get_io(&self) -> &I { &self.io }
+
+    /// Returns the I/O bus the CPU is attached to, mutably.
+    pub fn get_io_mut(&mut self) -> &mut I { &mut self.io }
+
+    /// Reads the byte at `address` through the memory bus.
+    pub fn get_mem(&mut self, address: u16) -> u8 {
+        self.mem.read(address)
+    }
+
+    /// Writes `value` to `address` through the memory bus.
+    pub fn set_mem(&mut self, address: u16, value: u8) {
+        self.mem.write(address, value);
+    }
+
+    /// Returns the carry flag.
+    pub fn get_carry(&self) -> bool {
+        self.regs[Reg::F] & 0b00000001 != 0
+    }
+
+    /// Returns the add/subtract flag.
+    pub fn get_add_subtract(&self) -> bool {
+        self.regs[Reg::F] & 0b00000010 != 0
+    }
+
+    /// Returns the parity/overflow flag.
+    pub fn get_parity_overflow(&self) -> bool {
+        self.regs[Reg::F] & 0b00000100 != 0
+    }
+
+    /// Returns the half carry flag.
+    pub fn get_half_carry(&self) -> bool {
+        self.regs[Reg::F] & 0b00010000 != 0
+    }
+
+    /// Returns the zero flag.
+    pub fn get_zero(&self) -> bool {
+        self.regs[Reg::F] & 0b01000000 != 0
+    }
+
+    /// Returns the sign flag.
+    pub fn get_sign(&self) -> bool {
+        self.regs[Reg::F] & 0b10000000 != 0
+    }
+
+    /// Sets the carry flag.
+    pub fn set_carry(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b00000001; }
         else { self.regs[Reg::F] &= 0b11111110; }
     }
 
-    fn set_add_subtract(&mut self, value: bool) {
+    /// Sets the add/subtract flag.
+    pub fn set_add_subtract(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b00000010; }
         else { self.regs[Reg::F] &= 0b11111101; }
     }
 
-    fn set_parity_overflow(&mut self, value: bool) {
+    /// Sets the parity/overflow flag.
+    pub fn set_parity_overflow(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b00000100; }
         else { self.regs[Reg::F] &= 0b11111011; }
     }
 
-    fn set_half_carry(&mut self, value: bool) {
+    /// Sets the half carry flag.
+    pub fn set_half_carry(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b00010000; }
         else { self.regs[Reg::F] &= 0b11101111; }
     }
 
-    fn set_zero(&mut self, value: bool) {
+    /// Sets the zero flag.
+    pub fn set_zero(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b01000000; }
         else { self.regs[Reg::F] &= 0b10111111; }
     }
 
-    fn set_sign(&mut self, value: bool) {
+    /// Sets the sign flag.
+    pub fn set_sign(&mut self, value: bool) {
         if value { self.regs[Reg::F] |= 0b10000000; }
         else { self.regs[Reg::F] &= 0b01111111; }
     }
 
+    fn set_undocumented(&mut self, value: u8) {
+        self.regs[Reg::F] = (self.regs[Reg::F] & 0b11010111) | (value & 0b00101000);
+    }
+
+    fn set_sign_zero_parity(&mut self, value: u8) {
+        self.set_sign(value & 0b10000000 != 0);
+        self.set_zero(value == 0);
+        self.set_parity_overflow(parity(value));
+        self.set_undocumented(value);
+    }
+
+    fn set_block_undocumented(&mut self, n: u8) {
+        // LDI and CPI copy bit 3 to X, as usual, but bit 1 to Y
+        let value = (n & 0b00001000) | ((n & 0b00000010) << 4);
+        self.set_undocumented(value);
+    }
+
+    // Rewinds PC so the instruction runs again, leaking PC into X/Y
+    fn repeat_block(&mut self) {
+        self.pc = self.pc.wrapping_sub(2);
+        self.cycles += 5;
+        let pc_high = (self.pc >> 8) as u8;
+        self.set_undocumented(pc_high);
+    }
+
+    fn block_io(&mut self, value: u8, k: u16, repeat: bool) {
+        let b = self.regs[Reg::B];
+        self.set_sign(b & 0b10000000 != 0);
+        self.set_zero(b == 0);
+        self.set_undocumented(b);
+        self.set_add_subtract(value & 0b10000000 != 0);
+        self.set_half_carry(k > 0xFF);
+        self.set_carry(k > 0xFF);
+        self.set_parity_overflow(parity((k & 0x07) as u8 ^ b));
+
+        if repeat && b != 0 {
+            self.repeat_block();
+
+            // An interrupted repeat also adjusts P/V and H as if B were still
+            // being decremented.
+            let parity_overflow = self.get_parity_overflow();
+            if self.get_carry() {
+                if value & 0b10000000 != 0 {
+                    self.set_parity_overflow(parity_overflow ^ !parity(b.wrapping_sub(1) & 0x07));
+                    self.set_half_carry(b & 0x0F == 0x00);
+                } else {
+                    self.set_parity_overflow(parity_overflow ^ !parity(b.wrapping_add(1) & 0x07));
+                    self.set_half_carry(b & 0x0F == 0x0F);
+                }
+            } else {
+                self.set_parity_overflow(parity_overflow ^ !parity(b & 0x07));
+            }
+        }
+    }
+
+    fn set_memptr_a(&mut self, address: u16) {
+        // Stores through A leave A in the high byte and only bump the low one
+        let low = address.wrapping_add(1) & 0x00FF;
+        self.memptr = ((self.regs[Reg::A] as u16) << 8) | low;
+    }
+
+    fn index_address(&mut self, index: u16, displacement: Displacement) -> u16 {
+        let address = index.wrapping_add(displacement as i16 as u16);
+        self.memptr = address;
+        address
+    }
+
+    fn add_a(&mut self, value: u8, carry: bool) {
+        let a = self.regs[Reg::A];
+        let carry = carry as u8;
+        let result = a.wrapping_add(value).wrapping_add(carry);
+        self.set_sign(result & 0b10000000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry((a & 0x0F) + (value & 0x0F) + carry > 0x0F);
+        self.set_parity_overflow((a ^ value) & 0b10000000 == 0 && (a ^ result) & 0b10000000 != 0);
+        self.set_add_subtract(false);
+        self.set_carry(a as u16 + value as u16 + carry as u16 > 0xFF);
+        self.set_undocumented(result);
+        self.regs[Reg::A] = result;
+    }
+
+    fn subtract(&mut self, value: u8, carry: bool) -> u8 {
+        let a = self.regs[Reg::A];
+        let carry = carry as u8;
+        let result = a.wrapping_sub(value).wrapping_sub(carry);
+        self.set_sign(result & 0b10000000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry((a & 0x0F) < (value & 0x0F) + carry);
+        self.set_parity_overflow((a ^ value) & 0b10000000 != 0 && (a ^ result) & 0b10000000 != 0);
+        self.set_add_subtract(true);
+        self.set_carry((a as u16) < value as u16 + carry as u16);
+        result
+    }
+
+    fn sub_a(&mut self, value: u8, carry: bool) {
+        let result = self.subtract(value, carry);
+        self.set_undocumented(result);
+        self.regs[Reg::A] = result;
+    }
+
+    fn cp_a(&mut self, value: u8) {
+        self.subtract(value, false);
+        // CP takes the undocumented flags from the operand, not the result
+        self.set_undocumented(value);
+    }
+
+    fn logic_a(&mut self, result: u8, half_carry: bool) {
+        self.set_sign_zero_parity(result);
+        self.set_half_carry(half_carry);
+        self.set_add_subtract(false);
+        self.set_carry(false);
+        self.regs[Reg::A] = result;
+    }
+
+    fn and_a(&mut self, value: u8) {
+        let result = self.regs[Reg::A] & value;
+        self.logic_a(result, true);
+    }
+
+    fn or_a(&mut self, value: u8) {
+        let result = self.regs[Reg::A] | value;
+        self.logic_a(result, false);
+    }
+
+    fn xor_a(&mut self, value: u8) {
+        let result = self.regs[Reg::A] ^ value;
+        self.logic_a(result, false);
+    }
+
+    fn daa(&mut self) {
+        let a = self.regs[Reg::A];
+        let mut correction = 0;
+        let mut carry = self.get_carry();
+        if self.get_half_carry() || a & 0x0F > 0x09 {
+            correction |= 0x06;
+        }
+        if carry || a > 0x99 {
+            correction |= 0x60;
+            carry = true;
+        }
+
+        let result = if self.get_add_subtract() {
+            self.set_half_carry(self.get_half_carry() && a & 0x0F < 0x06);
+            a.wrapping_sub(correction)
+        } else {
+            self.set_half_carry(a & 0x0F > 0x09);
+            a.wrapping_add(correction)
+        };
+        self.set_sign_zero_parity(result);
+        self.set_carry(carry);
+        self.regs[Reg::A] = result;
+    }
+
+    fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.set_sign(result & 0b10000000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry(value & 0x0F == 0x0F);
+        self.set_parity_overflow(value == 0x7F);
+        self.set_add_subtract(false);
+        self.set_undocumented(result);
+        result
+    }
+
+    fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.set_sign(result & 0b10000000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry(value & 0x0F == 0x00);
+        self.set_parity_overflow(value == 0x80);
+        self.set_add_subtract(true);
+        self.set_undocumented(result);
+        result
+    }
+
+    fn add_u16(&mut self, a: u16, value: u16) -> u16 {
+        let result = a.wrapping_add(value);
+        self.set_half_carry((a & 0x0FFF) + (value & 0x0FFF) > 0x0FFF);
+        self.set_add_subtract(false);
+        self.set_carry(a as u32 + value as u32 > 0xFFFF);
+        self.set_undocumented((result >> 8) as u8);
+        self.memptr = a.wrapping_add(1);
+        result
+    }
+
+    fn adc_hl(&mut self, value: u16) {
+        let hl = self.get_reg_pair(Reg::H, Reg::L);
+        let carry = self.get_carry() as u16;
+        let result = hl.wrapping_add(value).wrapping_add(carry);
+        self.set_sign(result & 0x8000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry((hl & 0x0FFF) + (value & 0x0FFF) + carry > 0x0FFF);
+        self.set_parity_overflow((hl ^ value) & 0x8000 == 0 && (hl ^ result) & 0x8000 != 0);
+        self.set_add_subtract(false);
+        self.set_carry(hl as u32 + value as u32 + carry as u32 > 0xFFFF);
+        self.set_undocumented((result >> 8) as u8);
+        self.memptr = hl.wrapping_add(1);
+        self.set_reg_pair(Reg::H, Reg::L, result);
+    }
+
+    fn sbc_hl(&mut self, value: u16) {
+        let hl = self.get_reg_pair(Reg::H, Reg::L);
+        let carry = self.get_carry() as u16;
+        let result = hl.wrapping_sub(value).wrapping_sub(carry);
+        self.set_sign(result & 0x8000 != 0);
+        self.set_zero(result == 0);
+        self.set_half_carry((hl & 0x0FFF) < (value & 0x0FFF) + carry);
+        self.set_parity_overflow((hl ^ value) & 0x8000 != 0 && (hl ^ result) & 0x8000 != 0);
+        self.set_add_subtract(true);
+        self.set_carry((hl as u32) < value as u32 + carry as u32);
+        self.set_undocumented((result >> 8) as u8);
+        self.memptr = hl.wrapping_add(1);
+        self.set_reg_pair(Reg::H, Reg::L, result);
+    }
+
+    fn rotate_a(&mut self, result: u8, carry: bool) {
+        // Unlike the CB rotates these leave S, Z and P/V alone
+        self.set_half_carry(false);
+        self.set_add_subtract(false);
+        self.set_carry(carry);
+        self.set_undocumented(result);
+        self.regs[Reg::A] = result;
+    }
+
+    fn rotate_digit(&mut self, a: u8) {
+        self.set_sign_zero_parity(a);
+        self.set_half_carry(false);
+        self.set_add_subtract(false);
+        self.regs[Reg::A] = a;
+    }
+
+    fn shift(&mut self, result: u8, carry: bool) -> u8 {
+        self.set_sign_zero_parity(result);
+        self.set_half_carry(false);
+        self.set_add_subtract(false);
+        self.set_carry(carry);
+        result
+    }
+
+    fn rlc(&mut self, value: u8) -> u8 {
+        self.shift(value.rotate_left(1), value & 0b10000000 != 0)
+    }
+
+    fn rrc(&mut self, value: u8) -> u8 {
+        self.shift(value.rotate_right(1), value & 0b00000001 != 0)
+    }
+
+    fn rl(&mut self, value: u8) -> u8 {
+        let carry = self.get_carry() as u8;
+        self.shift((value << 1) | carry, value & 0b10000000 != 0)
+    }
+
+    fn rr(&mut self, value: u8) -> u8 {
+        let carry = self.get_carry() as u8;
+        self.shift((value >> 1) | (carry << 7), value & 0b00000001 != 0)
+    }
+
+    fn sla(&mut self, value: u8) -> u8 {
+        self.shift(value << 1, value & 0b10000000 != 0)
+    }
+
+    fn sra(&mut self, value: u8) -> u8 {
+        self.shift((value >> 1) | (value & 0b10000000), value & 0b00000001 != 0)
+    }
+
+    fn sll(&mut self, value: u8) -> u8 {
+        // The undocumented SLL shifts a one into bit 0
+        self.shift((value << 1) | 1, value & 0b10000000 != 0)
+    }
+
+    fn srl(&mut self, value: u8) -> u8 {
+        self.shift(value >> 1, value & 0b00000001 != 0)
+    }
+
+    fn bit(&mut self, bit: Bit, value: u8, undocumented: u8) {
+        let result = value & mask(bit);
+        self.set_sign(result & 0b10000000 != 0);
+        self.set_zero(result == 0);
+        self.set_parity_overflow(result == 0);
+        self.set_half_carry(true);
+        self.set_add_subtract(false);
+        self.set_undocumented(undocumented);
+    }
+
+    fn condition(&self, condition: Condition) -> bool {
+        match condition {
+            Condition::NonZero => !self.get_zero(),
+            Condition::Zero => self.get_zero(),
+            Condition::NoCarry => !self.get_carry(),
+            Condition::Carry => self.get_carry(),
+            Condition::ParityOdd => !self.get_parity_overflow(),
+            Condition::ParityEven => self.get_parity_overflow(),
+            Condition::PositiveSign => !self.get_sign(),
+            Condition::NegativeSign => self.get_sign(),
+        }
+    }
+
+    fn jump_relative(&mut self, displacement: Displacement) {
+        self.pc = self.pc.wrapping_add(displacement as i16 as u16);
+        self.memptr = self.pc;
+    }
+
+    fn jump_relative_if(&mut self, condition: bool, displacement: Displacement) {
+        if condition {
+            self.jump_relative(displacement);
+            self.cycles += 5;
+        }
+    }
+
+    fn push(&mut self, value: u16) {
+        self.sp = self.sp.wrapping_sub(2);
+        let address = self.sp;
+        self.set_mem_u16(address, value);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let address = self.sp;
+        let value = self.get_mem_u16(address);
+        self.sp = self.sp.wrapping_add(2);
+        value
+    }
+
+    fn call(&mut self, address: u16) {
+        let pc = self.pc;
+        self.push(pc);
+        self.pc = address;
+        self.memptr = address;
+    }
+
+    fn ret(&mut self) {
+        self.pc = self.pop();
+        self.memptr = self.pc;
+    }
+
     fn get_reg_pair(&self, reg1: Reg, reg2: Reg) -> u16 {
         ((self.regs[reg1] as u16) << 8) + self.regs[reg2] as u16
     }
 
-    fn get_big_reg(&self, reg: BigReg) -> u16 {
+    /// Returns the value of a 16-bit register or register pair.
+    pub fn get_big_reg(&self, reg: BigReg) -> u16 {
         match reg {
             BigReg::BC => self.get_reg_pair(Reg::B, Reg::C),
             BigReg::DE => self.get_reg_pair(Reg::D, Reg::E),
@@ -99,7 +691,8 @@ impl Z80 {
         self.regs[reg2] = (value & 0x00FF) as u8;
     }
 
-    fn set_big_reg(&mut self, reg: BigReg, value: u16) {
+    /// Sets the value of a 16-bit register or register pair.
+    pub fn set_big_reg(&mut self, reg: BigReg, value: u16) {
         match reg {
             BigReg::BC => self.set_reg_pair(Reg::B, Reg::C, value),
             BigReg::DE => self.set_reg_pair(Reg::D, Reg::E, value),
@@ -112,93 +705,250 @@ impl Z80 {
     }
 
     fn get_mem_u16(&mut self, address: u16) -> u16 {
-        ((self.mem[address as usize] as u16) << 8) + self.mem[(address + 1) as usize] as u16
+        let low = self.get_mem(address) as u16;
+        let high = self.get_mem(address.wrapping_add(1)) as u16;
+        (high << 8) + low
     }
 
     fn set_mem_u16(&mut self, address: u16, value: u16) {
-        self.mem[address as usize] = (value & 0xFF00).wrapping_shr(8) as u8;
-        self.mem[(address + 1) as usize] = (value & 0x00FF) as u8;
+        self.set_mem(address, (value & 0x00FF) as u8);
+        self.set_mem(address.wrapping_add(1), (value & 0xFF00).wrapping_shr(8) as u8);
+    }
+
+    fn increment_r(&mut self, m1_cycles: u8) {
+        // Only the low 7 bits count, bit 7 keeps whatever was loaded
+        self.r = (self.r & 0b10000000) | (self.r.wrapping_add(m1_cycles) & 0b01111111);
+    }
+
+    fn ld_a_ir(&mut self, value: u8) {
+        self.regs[Reg::A] = value;
+        self.set_sign(value & 0b10000000 != 0);
+        self.set_zero(value == 0);
+        self.set_half_carry(false);
+        self.set_add_subtract(false);
+        let iff = self.iff2;
+        self.set_parity_overflow(iff);
+        self.set_undocumented(value);
+        self.after_ld_a_ir = true;
+    }
+
+    // Accepts a pending interrupt, returning the operation it performed
+    fn interrupt(&mut self) -> Option<Opcode> {
+        // No maskable interrupt is accepted right after EI
+        let ei_delay = self.ei_delay;
+        self.ei_delay = false;
+        let after_ld_a_ir = self.after_ld_a_ir;
+        self.after_ld_a_ir = false;
+
+        let accepted = self.nmi || (self.int.is_some() && self.iff1 && !ei_delay);
+        if accepted {
+            // The acknowledge is an M1 cycle of its own
+            self.increment_r(1);
+            // An interrupt accepted during LD A,I or LD A,R sees IFF2 already
+            // cleared, so P/V reads as zero
+            if after_ld_a_ir { self.set_parity_overflow(false); }
+        }
+
+        if self.nmi {
+            self.nmi = false;
+            self.halted = false;
+            self.iff1 = false;
+            self.cycles += 11;
+            self.call(0x0066);
+            return Some(Opcode::CALLNN(0x0066));
+        }
+
+        let data = match self.int {
+            Some(ref data) if self.iff1 && !ei_delay => data.clone(),
+            _ => return None,
+        };
+        self.halted = false;
+        self.iff1 = false;
+        self.iff2 = false;
+        match self.im {
+            0 => {
+                // The device supplies an instruction, normally an RST, which
+                // runs without advancing PC
+                let (_, op) = parse_op(&mut data.into_iter());
+                self.cycles += 2;
+                self.run_op(op);
+                Some(op)
+            },
+            1 => {
+                self.cycles += 13;
+                self.call(0x0038);
+                Some(Opcode::RETP(0x38))
+            },
+            _ => {
+                let low = data.first().cloned().unwrap_or(0xFF);
+                let vector = ((self.i as u16) << 8) | low as u16;
+                let address = self.get_mem_u16(vector);
+                self.cycles += 19;
+                self.call(address);
+                Some(Opcode::CALLNN(address))
+            },
+        }
+    }
+
+    /// Fetches the instruction at PC, advances PC past it and executes it.
+    /// Returns the executed instruction and the T-states it took. A pending
+    /// interrupt is accepted instead, returning the call it performed. While
+    /// halted, the CPU idles for 4 T-states per step and reports a NOP.
+    pub fn step(&mut self) -> (Opcode, u32) {
+        let start = self.cycles;
+        // Interrupts are not accepted between a prefix and its follower
+        let prefetched = self.prefetched.take();
+        if prefetched.is_none() {
+            if let Some(op) = self.interrupt() {
+                return (op, (self.cycles - start) as u32);
+            }
+        }
+        if self.halted {
+            // PC already points past HALT, so it is where the CPU resumes
+            self.increment_r(1);
+            self.cycles += 4;
+            return (Opcode::NOP, 4);
+        }
+
+        let pc = self.pc;
+        let mut m1_cycles = 0;
+        let mut follower = None;
+        let (size, op) = {
+            let mem = &mut self.mem;
+            let mut prefixed = false;
+            parse_op(&mut (0u16..4).map(|offset| {
+                let address = pc.wrapping_add(offset);
+                if offset == 0 {
+                    let byte = prefetched.unwrap_or_else(|| {
+                        m1_cycles += 1;
+                        mem.fetch_opcode(address)
+                    });
+                    prefixed = is_prefix(byte);
+                    byte
+                } else if offset == 1 && prefixed {
+                    let byte = mem.fetch_opcode(address);
+                    m1_cycles += 1;
+                    follower = Some(byte);
+                    byte
+                } else {
+                    mem.read(address)
+                }
+            }))
+        };
+        self.pc = pc.wrapping_add(size as u16);
+        // An unused prefix is a one byte NOP, the next step executes the
+        // follower it already fetched
+        if size == 1 {
+            self.prefetched = follower;
+        }
+        self.increment_r(m1_cycles);
+        // Undefined ED opcodes and DD/FD HALT also pay for the prefix fetch
+        if size == 2 && (op == Opcode::NOP || op == Opcode::HALT) {
+            self.cycles += 4;
+        }
+
+        self.run_op(op);
+        (op, (self.cycles - start) as u32)
+    }
+
+    /// Executes instructions until at least `cycles` T-states have elapsed.
+    /// Returns the T-states actually executed, which may overshoot by the
+    /// length of the last instruction.
+    pub fn run_for_cycles(&mut self, cycles: u64) -> u64 {
+        let start = self.cycles;
+        while self.cycles - start < cycles {
+            self.step();
+        }
+        self.cycles - start
     }
 
-    fn flip_u16(&self, value: u16) -> u16 {
-        (value & 0xFF00).wrapping_shr(8) + (value & 0x00FF).wrapping_shl(8)
+    /// Executes instructions until `predicate` returns true, checking it before
+    /// every instruction. Returns the T-states executed.
+    pub fn run_until<F>(&mut self, mut predicate: F) -> u64 where F: FnMut(&Z80<M, I>) -> bool {
+        let start = self.cycles;
+        while !predicate(self) {
+            self.step();
+        }
+        self.cycles - start
     }
 
-    fn run_op(&mut self, op: Opcode) {
+    /// Executes an already decoded instruction. PC is expected to point past it.
+    pub fn run_op(&mut self, op: Opcode) {
+        self.cycles += op.cycles() as u64;
         match op {
             Opcode::LDRR(reg1, reg2) => self.regs[reg1] = self.regs[reg2],
             Opcode::LDRN(reg1, value) => self.regs[reg1] = value,
             Opcode::LDRHL(reg1) => {
                 let idx = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[reg1] = self.mem[idx as usize];
+                self.regs[reg1] = self.get_mem(idx);
             },
             Opcode::LDRIXD(reg1, displacement) => {
-                let idx = self.ix + displacement as u16;
-                self.regs[reg1] = self.mem[idx as usize];
+                let idx = self.index_address(self.ix, displacement);
+                self.regs[reg1] = self.get_mem(idx);
             },
             Opcode::LDRIYD(reg1, displacement) => {
-                let idx = self.iy + displacement as u16;
-                self.regs[reg1] = self.mem[idx as usize];
+                let idx = self.index_address(self.iy, displacement);
+                self.regs[reg1] = self.get_mem(idx);
             },
             Opcode::LDHLR(reg1) => {
                 let idx = self.get_reg_pair(Reg::H, Reg::L);
-                self.mem[idx as usize] = self.regs[reg1];
+                self.set_mem(idx, self.regs[reg1]);
             },
             Opcode::LDIXDR(displacement, reg1) => {
-                let idx = self.ix + displacement as u16;
-                self.mem[idx as usize] = self.regs[reg1];
+                let idx = self.index_address(self.ix, displacement);
+                self.set_mem(idx, self.regs[reg1]);
             },
             Opcode::LDIYDR(displacement, reg1) => {
-                let idx = self.iy + displacement as u16;
-                self.mem[idx as usize] = self.regs[reg1];
+                let idx = self.index_address(self.iy, displacement);
+                self.set_mem(idx, self.regs[reg1]);
             },
             Opcode::LDHLN(value) => {
                 let idx = self.get_reg_pair(Reg::H, Reg::L);
-                self.mem[idx as usize] = value;
+                self.set_mem(idx, value);
             },
             Opcode::LDIXDN(displacement, value) => {
-                let idx = self.ix + displacement as u16;
-                self.mem[idx as usize] = value;
+                let idx = self.index_address(self.ix, displacement);
+                self.set_mem(idx, value);
             },
             Opcode::LDIYDN(displacement, value) => {
-                let idx = self.iy + displacement as u16;
-                self.mem[idx as usize] = value;
+                let idx = self.index_address(self.iy, displacement);
+                self.set_mem(idx, value);
             },
             Opcode::LDABC => {
                 let idx = self.get_reg_pair(Reg::B, Reg::C);
-                self.regs[Reg::A] = self.mem[idx as usize];
+                self.regs[Reg::A] = self.get_mem(idx);
+                self.memptr = idx.wrapping_add(1);
             },
             Opcode::LDADE => {
                 let idx = self.get_reg_pair(Reg::D, Reg::E);
-                self.regs[Reg::A] = self.mem[idx as usize];
+                self.regs[Reg::A] = self.get_mem(idx);
+                self.memptr = idx.wrapping_add(1);
+            },
+            Opcode::LDANN(idx) => {
+                self.regs[Reg::A] = self.get_mem(idx);
+                self.memptr = idx.wrapping_add(1);
             },
-            Opcode::LDANN(idx) => self.regs[Reg::A] = self.mem[idx as usize],
             Opcode::LDBCA => {
                 let idx = self.get_reg_pair(Reg::B, Reg::C);
-                self.mem[idx as usize] = self.regs[Reg::A];
+                self.set_mem(idx, self.regs[Reg::A]);
+                self.set_memptr_a(idx);
             },
             Opcode::LDDEA => {
                 let idx = self.get_reg_pair(Reg::D, Reg::E);
-                self.mem[idx as usize] = self.regs[Reg::A];
+                self.set_mem(idx, self.regs[Reg::A]);
+                self.set_memptr_a(idx);
+            },
+            Opcode::LDNNA(idx) => {
+                self.set_mem(idx, self.regs[Reg::A]);
+                self.set_memptr_a(idx);
             },
-            Opcode::LDNNA(idx) => self.mem[idx as usize] = self.regs[Reg::A],
             Opcode::LDAI => {
-                self.regs[Reg::A] = self.i;
-                if (self.i & 0b10000000) > 0 { self.set_sign(true); }
-                if self.i == 0 { self.set_zero(true); }
-                self.set_half_carry(false);
-                self.set_add_subtract(false);
-                let iff = self.iff2;
-                self.set_parity_overflow(iff);
+                let value = self.i;
+                self.ld_a_ir(value);
             },
             Opcode::LDAR => {
-                self.regs[Reg::A] = self.r;
-                if (self.r & 0b10000000) > 0 { self.set_sign(true); }
-                if self.r == 0 { self.set_zero(true); }
-                self.set_half_carry(false);
-                self.set_add_subtract(false);
-                let iff = self.iff2;
-                self.set_parity_overflow(iff);
+                let value = self.r;
+                self.ld_a_ir(value);
             },
             Opcode::LDIA => self.i = self.regs[Reg::A],
             Opcode::LDRA => self.r = self.regs[Reg::A],
@@ -206,460 +956,983 @@ impl Z80 {
             Opcode::LDIXNN(value) => self.ix = value,
             Opcode::LDIYNN(value) => self.iy = value,
             Opcode::LDHLNN(address) => {
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
+                let value = self.get_mem_u16(address);
                 self.set_reg_pair(Reg::H, Reg::L, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDDDNN2(big_reg, address) => {
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
+                let value = self.get_mem_u16(address);
                 self.set_big_reg(big_reg, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDIXNN2(address) => {
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
+                let value = self.get_mem_u16(address);
                 self.ix = value;
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDIYNN2(address) => {
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
+                let value = self.get_mem_u16(address);
                 self.iy = value;
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDNNHL(address) => {
-                let mut value = self.get_reg_pair(Reg::H, Reg::L);
-                value = self.flip_u16(value);
+                let value = self.get_reg_pair(Reg::H, Reg::L);
                 self.set_mem_u16(address, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDNNDD(address, big_reg) => {
-                let mut value = self.get_big_reg(big_reg);
-                value = self.flip_u16(value);
+                let value = self.get_big_reg(big_reg);
                 self.set_mem_u16(address, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDNNIX(address) => {
-                let mut value = self.ix;
-                value = self.flip_u16(value);
+                let value = self.ix;
                 self.set_mem_u16(address, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDNNIY(address) => {
-                let mut value = self.iy;
-                value = self.flip_u16(value);
+                let value = self.iy;
                 self.set_mem_u16(address, value);
+                self.memptr = address.wrapping_add(1);
             },
             Opcode::LDSPHL => self.sp = self.get_reg_pair(Reg::H, Reg::L),
             Opcode::LDSPIX => self.sp = self.ix,
             Opcode::LDSPIY => self.sp = self.iy,
             Opcode::PUSHQQ(big_reg) => {
-                let mut value = self.get_big_reg(big_reg);
-                value = self.flip_u16(value);
-                self.sp -= 2;
-                let address = self.sp;
-                self.set_mem_u16(address, value);
+                let value = self.get_big_reg(big_reg);
+                self.push(value);
             },
             Opcode::PUSHIX => {
-                let mut value = self.ix;
-                value = self.flip_u16(value);
-                self.sp -= 2;
-                let address = self.sp;
-                self.set_mem_u16(address, value);
+                let value = self.ix;
+                self.push(value);
             },
             Opcode::PUSHIY => {
-                let mut value = self.iy;
-                value = self.flip_u16(value);
-                self.sp -= 2;
-                let address = self.sp;
-                self.set_mem_u16(address, value);
+                let value = self.iy;
+                self.push(value);
             },
             Opcode::POPQQ(big_reg) => {
-                let address = self.sp;
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
-                self.sp += 2;
+                let value = self.pop();
                 self.set_big_reg(big_reg, value);
             },
             Opcode::POPIX => {
-                let address = self.sp;
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
-                self.sp += 2;
+                let value = self.pop();
                 self.ix = value;
             },
             Opcode::POPIY => {
-                let address = self.sp;
-                let mut value = self.get_mem_u16(address);
-                value = self.flip_u16(value);
-                self.sp += 2;
+                let value = self.pop();
                 self.iy = value;
             },
             Opcode::EXDEHL => {
-                let d = self.regs[Reg::D];
-                self.regs[Reg::D] = self.regs[Reg::H];
-                self.regs[Reg::H] = d;
-                let e = self.regs[Reg::E];
-                self.regs[Reg::E] = self.regs[Reg::L];
-                self.regs[Reg::L] = e;
+                self.regs.swap(Reg::D as usize, Reg::H as usize);
+                self.regs.swap(Reg::E as usize, Reg::L as usize);
             },
             Opcode::EXAFAF2 => {
-                let a = self.regs[Reg::A];
-                self.regs[Reg::A] = self.regs[Reg::A2];
-                self.regs[Reg::A2] = a;
-                let f = self.regs[Reg::F];
-                self.regs[Reg::F] = self.regs[Reg::F2];
-                self.regs[Reg::F2] = f;
+                self.regs.swap(Reg::A as usize, Reg::A2 as usize);
+                self.regs.swap(Reg::F as usize, Reg::F2 as usize);
             },
             Opcode::EXX => {
-                let b = self.regs[Reg::B];
-                self.regs[Reg::B] = self.regs[Reg::B2];
-                self.regs[Reg::B2] = b;
-                let c = self.regs[Reg::C];
-                self.regs[Reg::C] = self.regs[Reg::C2];
-                self.regs[Reg::C2] = c;
-
-                let d = self.regs[Reg::D];
-                self.regs[Reg::D] = self.regs[Reg::D2];
-                self.regs[Reg::D2] = d;
-                let e = self.regs[Reg::E];
-                self.regs[Reg::E] = self.regs[Reg::E2];
-                self.regs[Reg::E2] = e;
-
-                let h = self.regs[Reg::H];
-                self.regs[Reg::H] = self.regs[Reg::H2];
-                self.regs[Reg::H2] = h;
-                let l = self.regs[Reg::L];
-                self.regs[Reg::L] = self.regs[Reg::L2];
-                self.regs[Reg::L2] = l;
+                self.regs.swap(Reg::B as usize, Reg::B2 as usize);
+                self.regs.swap(Reg::C as usize, Reg::C2 as usize);
+
+                self.regs.swap(Reg::D as usize, Reg::D2 as usize);
+                self.regs.swap(Reg::E as usize, Reg::E2 as usize);
+
+                self.regs.swap(Reg::H as usize, Reg::H2 as usize);
+                self.regs.swap(Reg::L as usize, Reg::L2 as usize);
             },
             Opcode::EXSPHL => {
                 let address = self.sp;
-
-                let mut reg_value = self.get_big_reg(BigReg::HL);
-                reg_value = self.flip_u16(reg_value);
-
-                let mut mem_value = self.get_mem_u16(address);
-                mem_value = self.flip_u16(mem_value);
-
+                let reg_value = self.get_big_reg(BigReg::HL);
+                let mem_value = self.get_mem_u16(address);
                 self.set_mem_u16(address, reg_value);
                 self.set_big_reg(BigReg::HL, mem_value);
+                self.memptr = mem_value;
             },
             Opcode::EXSPIX => {
                 let address = self.sp;
-
-                let mut reg_value = self.ix;
-                reg_value = self.flip_u16(reg_value);
-
-                let mut mem_value = self.get_mem_u16(address);
-                mem_value = self.flip_u16(mem_value);
-
+                let reg_value = self.ix;
+                let mem_value = self.get_mem_u16(address);
                 self.set_mem_u16(address, reg_value);
                 self.ix = mem_value;
+                self.memptr = mem_value;
             },
             Opcode::EXSPIY => {
                 let address = self.sp;
-
-                let mut reg_value = self.iy;
-                reg_value = self.flip_u16(reg_value);
-
-                let mut mem_value = self.get_mem_u16(address);
-                mem_value = self.flip_u16(mem_value);
-
+                let reg_value = self.iy;
+                let mem_value = self.get_mem_u16(address);
                 self.set_mem_u16(address, reg_value);
                 self.iy = mem_value;
+                self.memptr = mem_value;
             },
-            Opcode::LDI => {
+            Opcode::LDI | Opcode::LDIR | Opcode::LDD | Opcode::LDDR => {
                 let address_hl = self.get_reg_pair(Reg::H, Reg::L);
                 let address_de = self.get_reg_pair(Reg::D, Reg::E);
-                self.mem[address_de as usize] = self.mem[address_hl as usize];
-                self.set_reg_pair(Reg::H, Reg::L, address_hl + 1);
-                self.set_reg_pair(Reg::D, Reg::E, address_de + 1);
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - 1);
-
-                self.set_half_carry(false);
-                if value_bc - 1 == 0 { self.set_parity_overflow(true); }
-                else { self.set_parity_overflow(false); }
-                self.set_add_subtract(false);
-            }
-            Opcode::LDIR => {
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                let address_de = self.get_reg_pair(Reg::D, Reg::E);
-                let mut counter = 0;
-                while value_bc > counter {
-                    self.mem[(address_de + counter) as usize] = self.mem[(address_hl + counter) as usize];
-                    counter += 1;
+                let value = self.get_mem(address_hl);
+                self.set_mem(address_de, value);
+                if op == Opcode::LDI || op == Opcode::LDIR {
+                    self.set_reg_pair(Reg::H, Reg::L, address_hl.wrapping_add(1));
+                    self.set_reg_pair(Reg::D, Reg::E, address_de.wrapping_add(1));
+                } else {
+                    self.set_reg_pair(Reg::H, Reg::L, address_hl.wrapping_sub(1));
+                    self.set_reg_pair(Reg::D, Reg::E, address_de.wrapping_sub(1));
                 }
-                self.set_reg_pair(Reg::H, Reg::L, address_hl + counter);
-                self.set_reg_pair(Reg::D, Reg::E, address_de + counter);
-                self.set_reg_pair(Reg::B, Reg::C, 0);
+                let value_bc = self.get_reg_pair(Reg::B, Reg::C).wrapping_sub(1);
+                self.set_reg_pair(Reg::B, Reg::C, value_bc);
 
                 self.set_half_carry(false);
-                self.set_parity_overflow(false);
+                self.set_parity_overflow(value_bc != 0);
                 self.set_add_subtract(false);
-            },
-            Opcode::LDD => {
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                let address_de = self.get_reg_pair(Reg::D, Reg::E);
-                self.mem[address_de as usize] = self.mem[address_hl as usize];
-                self.set_reg_pair(Reg::H, Reg::L, address_hl - 1);
-                self.set_reg_pair(Reg::D, Reg::E, address_de - 1);
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - 1);
-                // TODO: Set flags
-            },
-            Opcode::LDDR => {
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                let address_de = self.get_reg_pair(Reg::D, Reg::E);
-                let mut counter = 0;
-                while value_bc > counter {
-                    self.mem[(address_de - counter) as usize] = self.mem[(address_hl - counter) as usize];
-                    counter += 1;
+                let n = value.wrapping_add(self.regs[Reg::A]);
+                self.set_block_undocumented(n);
+
+                if (op == Opcode::LDIR || op == Opcode::LDDR) && value_bc != 0 {
+                    self.repeat_block();
+                    self.memptr = self.pc.wrapping_add(1);
                 }
-                self.set_reg_pair(Reg::H, Reg::L, address_hl - counter);
-                self.set_reg_pair(Reg::D, Reg::E, address_de - counter);
-                self.set_reg_pair(Reg::B, Reg::C, 0);
-                // TODO: Set flags
-            },
-            Opcode::CPI => {
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                self.set_reg_pair(Reg::H, Reg::L, address_hl + 1);
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - 1);
-                // TODO: Set flags
             },
-            Opcode::CPIR => {
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
+            Opcode::CPI | Opcode::CPIR | Opcode::CPD | Opcode::CPDR => {
                 let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                let mut counter = 0;
-
-                // TODO: This behavior doesn't emulate the behavior of the CPU (Review the Z80 User
-                // manual description of the instruction)
-                loop {
-                    if self.mem[(address_hl + counter) as usize] == self.regs[Reg::A] {
-                        break;
-                    }
-                    if value_bc - counter == 0 { break; }
-                    counter += 1;
+                let value = self.get_mem(address_hl);
+                if op == Opcode::CPI || op == Opcode::CPIR {
+                    self.set_reg_pair(Reg::H, Reg::L, address_hl.wrapping_add(1));
+                } else {
+                    self.set_reg_pair(Reg::H, Reg::L, address_hl.wrapping_sub(1));
                 }
-                counter += 1;
-                self.set_reg_pair(Reg::H, Reg::L, address_hl + counter);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - counter);
+                let value_bc = self.get_reg_pair(Reg::B, Reg::C).wrapping_sub(1);
+                self.set_reg_pair(Reg::B, Reg::C, value_bc);
 
-                // TODO: Set flags
-            },
-            Opcode::CPD => {
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                self.set_reg_pair(Reg::H, Reg::L, address_hl - 1);
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - 1);
-                // TODO: Set flags
-            },
-            Opcode::CPDR => {
-                let value_bc = self.get_reg_pair(Reg::B, Reg::C);
-                let address_hl = self.get_reg_pair(Reg::H, Reg::L);
-                let mut counter = 0;
-
-                // TODO: This behavior doesn't emulate the behavior of the CPU (Review the Z80 User
-                // manual description of the instruction)
-                loop {
-                    if self.mem[(address_hl - counter) as usize] == self.regs[Reg::A] {
-                        break;
-                    }
-                    if value_bc - counter == 0 { break; }
-                    counter += 1;
+                let carry = self.get_carry();
+                let result = self.subtract(value, false);
+                self.set_carry(carry);
+                self.set_parity_overflow(value_bc != 0);
+                let n = result.wrapping_sub(self.get_half_carry() as u8);
+                self.set_block_undocumented(n);
+
+                if op == Opcode::CPI || op == Opcode::CPIR {
+                    self.memptr = self.memptr.wrapping_add(1);
+                } else {
+                    self.memptr = self.memptr.wrapping_sub(1);
                 }
-                counter += 1;
-                self.set_reg_pair(Reg::H, Reg::L, address_hl - counter);
-                self.set_reg_pair(Reg::B, Reg::C, value_bc - counter);
 
-                // TODO: Set flags
+                if (op == Opcode::CPIR || op == Opcode::CPDR) && value_bc != 0 && result != 0 {
+                    self.repeat_block();
+                    self.memptr = self.pc.wrapping_add(1);
+                }
             },
             Opcode::ADDAR(reg) => {
-                self.regs[Reg::A] += self.regs[reg];
-                // TODO: Set flags
-            },
-            Opcode::ADDAN(value) => {
-                self.regs[Reg::A] += value;
-                // TODO: Set flags
+                let value = self.regs[reg];
+                self.add_a(value, false);
             },
+            Opcode::ADDAN(value) => self.add_a(value, false),
             Opcode::ADDAHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] += self.mem[address as usize];
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                self.add_a(value, false);
             },
             Opcode::ADDAIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] += self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.add_a(value, false);
             },
             Opcode::ADDAIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] += self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.add_a(value, false);
             },
-            Opcode::SUBAR(reg) => {
-                self.regs[Reg::A] -= self.regs[reg];
-                // TODO: Set flags
+            Opcode::ADCAR(reg) => {
+                let value = self.regs[reg];
+                let carry = self.get_carry();
+                self.add_a(value, carry);
+            },
+            Opcode::ADCAN(value) => {
+                let carry = self.get_carry();
+                self.add_a(value, carry);
             },
-            Opcode::SUBAN(value) => {
-                self.regs[Reg::A] -= value;
-                // TODO: Set flags
+            Opcode::ADCAHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.add_a(value, carry);
+            },
+            Opcode::ADCAIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.add_a(value, carry);
+            },
+            Opcode::ADCAIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.add_a(value, carry);
+            },
+            Opcode::SUBAR(reg) => {
+                let value = self.regs[reg];
+                self.sub_a(value, false);
             },
+            Opcode::SUBAN(value) => self.sub_a(value, false),
             Opcode::SUBAHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] -= self.mem[address as usize];
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                self.sub_a(value, false);
             },
             Opcode::SUBAIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] -= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.sub_a(value, false);
             },
             Opcode::SUBAIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] -= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.sub_a(value, false);
             },
             Opcode::SBCAR(reg) => {
-                let carry = self.regs[Reg::F] & 0b00000001;
-                self.regs[Reg::A] -= self.regs[reg];
-                self.regs[Reg::A] -= carry;
-                // TODO: Set flags
+                let value = self.regs[reg];
+                let carry = self.get_carry();
+                self.sub_a(value, carry);
             },
             Opcode::SBCAN(value) => {
-                let carry = self.regs[Reg::F] & 0b00000001;
-                self.regs[Reg::A] -= value;
-                self.regs[Reg::A] -= carry;
-                // TODO: Set flags
+                let carry = self.get_carry();
+                self.sub_a(value, carry);
             },
             Opcode::SBCAHL => {
-                let carry = self.regs[Reg::F] & 0b00000001;
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] -= self.mem[address as usize];
-                self.regs[Reg::A] -= carry;
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.sub_a(value, carry);
             },
             Opcode::SBCAIXD(displacement) => {
-                let carry = self.regs[Reg::F] & 0b00000001;
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] -= self.mem[address as usize];
-                self.regs[Reg::A] -= carry;
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.sub_a(value, carry);
             },
             Opcode::SBCAIYD(displacement) => {
-                let carry = self.regs[Reg::F] & 0b00000001;
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] -= self.mem[address as usize];
-                self.regs[Reg::A] -= carry;
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let carry = self.get_carry();
+                self.sub_a(value, carry);
             },
             Opcode::ANDAR(reg) => {
-                self.regs[Reg::A] &= self.regs[reg];
-                // TODO: Set flags
-            },
-            Opcode::ANDAN(value) => {
-                self.regs[Reg::A] &= value;
-                // TODO: Set flags
+                let value = self.regs[reg];
+                self.and_a(value);
             },
+            Opcode::ANDAN(value) => self.and_a(value),
             Opcode::ANDAHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] &= self.mem[address as usize];
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                self.and_a(value);
             },
             Opcode::ANDAIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] &= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.and_a(value);
             },
             Opcode::ANDAIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] &= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.and_a(value);
             },
-            Opcode::ORAR(reg) => {
-                self.regs[Reg::A] |= self.regs[reg];
-                // TODO: Set flags
+            Opcode::XORAR(reg) => {
+                let value = self.regs[reg];
+                self.xor_a(value);
+            },
+            Opcode::XORAN(value) => self.xor_a(value),
+            Opcode::XORAHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                self.xor_a(value);
+            },
+            Opcode::XORAIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.xor_a(value);
             },
-            Opcode::ORAN(value) => {
-                self.regs[Reg::A] |= value;
-                // TODO: Set flags
+            Opcode::XORAIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.xor_a(value);
+            },
+            Opcode::ORAR(reg) => {
+                let value = self.regs[reg];
+                self.or_a(value);
             },
+            Opcode::ORAN(value) => self.or_a(value),
             Opcode::ORAHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] |= self.mem[address as usize];
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                self.or_a(value);
             },
             Opcode::ORAIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] |= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.or_a(value);
             },
             Opcode::ORAIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] |= self.mem[address as usize];
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.or_a(value);
             },
-            Opcode::XORAR(reg) => {
-                self.regs[Reg::A] ^= self.regs[reg];
-                // TODO: Set flags
-            },
-            Opcode::XORAN(value) => {
-                self.regs[Reg::A] ^= value;
-                // TODO: Set flags
+            Opcode::CPAR(reg) => {
+                let value = self.regs[reg];
+                self.cp_a(value);
             },
-            Opcode::XORAHL => {
+            Opcode::CPAN(value) => self.cp_a(value),
+            Opcode::CPAHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.regs[Reg::A] ^= self.mem[address as usize];
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                self.cp_a(value);
             },
-            Opcode::XORAIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.regs[Reg::A] ^= self.mem[address as usize];
-                // TODO: Set flags
+            Opcode::CPAIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.cp_a(value);
             },
-            Opcode::XORAIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.regs[Reg::A] ^= self.mem[address as usize];
-                // TODO: Set flags
-            },
-            Opcode::INCR(reg) => {
-                self.regs[reg] += 1;
-                // TODO: Set flags
+            Opcode::CPAIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.cp_a(value);
             },
+            Opcode::INCR(reg) => self.regs[reg] = self.inc(self.regs[reg]),
             Opcode::INCHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.mem[address as usize] += 1;
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                let result = self.inc(value);
+                self.set_mem(address, result);
             },
             Opcode::INCIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.mem[address as usize] += 1;
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.inc(value);
+                self.set_mem(address, result);
             },
             Opcode::INCIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.mem[address as usize] += 1;
-                // TODO: Set flags
-            },
-            Opcode::DECR(reg) => {
-                self.regs[reg] -= 1;
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.inc(value);
+                self.set_mem(address, result);
             },
+            Opcode::DECR(reg) => self.regs[reg] = self.dec(self.regs[reg]),
             Opcode::DECHL => {
                 let address = self.get_reg_pair(Reg::H, Reg::L);
-                self.mem[address as usize] -= 1;
-                // TODO: Set flags
+                let value = self.get_mem(address);
+                let result = self.dec(value);
+                self.set_mem(address, result);
             },
             Opcode::DECIXD(displacement) => {
-                let address = self.ix + displacement as u16;
-                self.mem[address as usize] -= 1;
-                // TODO: Set flags
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.dec(value);
+                self.set_mem(address, result);
             },
             Opcode::DECIYD(displacement) => {
-                let address = self.iy + displacement as u16;
-                self.mem[address as usize] -= 1;
-                // TODO: Set flags
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.dec(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLCA => {
+                let a = self.regs[Reg::A];
+                self.rotate_a(a.rotate_left(1), a & 0b10000000 != 0);
+            },
+            Opcode::RRCA => {
+                let a = self.regs[Reg::A];
+                self.rotate_a(a.rotate_right(1), a & 0b00000001 != 0);
+            },
+            Opcode::RLA => {
+                let a = self.regs[Reg::A];
+                let carry = self.get_carry() as u8;
+                self.rotate_a((a << 1) | carry, a & 0b10000000 != 0);
+            },
+            Opcode::RRA => {
+                let a = self.regs[Reg::A];
+                let carry = self.get_carry() as u8;
+                self.rotate_a((a >> 1) | (carry << 7), a & 0b00000001 != 0);
+            },
+            Opcode::RLD => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let a = self.regs[Reg::A];
+                self.set_mem(address, (value << 4) | (a & 0x0F));
+                self.rotate_digit((a & 0xF0) | (value >> 4));
+                self.memptr = address.wrapping_add(1);
+            },
+            Opcode::RRD => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let a = self.regs[Reg::A];
+                self.set_mem(address, (a << 4) | (value >> 4));
+                self.rotate_digit((a & 0xF0) | (value & 0x0F));
+                self.memptr = address.wrapping_add(1);
+            },
+            Opcode::RLCR(reg) => self.regs[reg] = self.rlc(self.regs[reg]),
+            Opcode::RLCHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.rlc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLCIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rlc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLCIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rlc(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RLCIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rlc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLCIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rlc(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RRCR(reg) => self.regs[reg] = self.rrc(self.regs[reg]),
+            Opcode::RRCHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.rrc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRCIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rrc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRCIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rrc(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RRCIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rrc(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRCIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rrc(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RLR(reg) => self.regs[reg] = self.rl(self.regs[reg]),
+            Opcode::RLHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.rl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rl(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RLIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RLIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rl(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RRR(reg) => self.regs[reg] = self.rr(self.regs[reg]),
+            Opcode::RRHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.rr(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rr(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.rr(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RRIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rr(value);
+                self.set_mem(address, result);
+            },
+            Opcode::RRIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.rr(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SLAR(reg) => self.regs[reg] = self.sla(self.regs[reg]),
+            Opcode::SLAHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.sla(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLAIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sla(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLAIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sla(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SLAIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sla(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLAIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sla(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SRAR(reg) => self.regs[reg] = self.sra(self.regs[reg]),
+            Opcode::SRAHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.sra(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRAIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sra(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRAIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sra(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SRAIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sra(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRAIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sra(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SLLR(reg) => self.regs[reg] = self.sll(self.regs[reg]),
+            Opcode::SLLHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.sll(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLLIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sll(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLLIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.sll(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SLLIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sll(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SLLIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.sll(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SRLR(reg) => self.regs[reg] = self.srl(self.regs[reg]),
+            Opcode::SRLHL => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let result = self.srl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRLIXD(displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.srl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRLIXDR(displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                let result = self.srl(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SRLIYD(displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.srl(value);
+                self.set_mem(address, result);
+            },
+            Opcode::SRLIYDR(displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                let result = self.srl(value);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::BITBR(bit, reg) => {
+                let value = self.regs[reg];
+                self.bit(bit, value, value);
+            },
+            Opcode::BITBHL(bit) => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                // X and Y leak from the high byte of the internal WZ register
+                let undocumented = (self.memptr >> 8) as u8;
+                self.bit(bit, value, undocumented);
+            },
+            Opcode::BITBIXD(bit, displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.bit(bit, value, (address >> 8) as u8);
+            },
+            Opcode::BITBIYD(bit, displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.bit(bit, value, (address >> 8) as u8);
+            },
+            Opcode::SETBR(bit, reg) => self.regs[reg] |= mask(bit),
+            Opcode::SETBHL(bit) => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                self.set_mem(address, value | mask(bit));
+            },
+            Opcode::SETBIXD(bit, displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.set_mem(address, value | mask(bit));
+            },
+            Opcode::SETBIXDR(bit, displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let result = self.get_mem(address) | mask(bit);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::SETBIYD(bit, displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.set_mem(address, value | mask(bit));
+            },
+            Opcode::SETBIYDR(bit, displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let result = self.get_mem(address) | mask(bit);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RESBR(bit, reg) => self.regs[reg] &= !mask(bit),
+            Opcode::RESBHL(bit) => {
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                self.set_mem(address, value & !mask(bit));
+            },
+            Opcode::RESBIXD(bit, displacement) => {
+                let address = self.index_address(self.ix, displacement);
+                let value = self.get_mem(address);
+                self.set_mem(address, value & !mask(bit));
+            },
+            Opcode::RESBIXDR(bit, displacement, reg) => {
+                let address = self.index_address(self.ix, displacement);
+                let result = self.get_mem(address) & !mask(bit);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::RESBIYD(bit, displacement) => {
+                let address = self.index_address(self.iy, displacement);
+                let value = self.get_mem(address);
+                self.set_mem(address, value & !mask(bit));
+            },
+            Opcode::RESBIYDR(bit, displacement, reg) => {
+                let address = self.index_address(self.iy, displacement);
+                let result = self.get_mem(address) & !mask(bit);
+                self.set_mem(address, result);
+                self.regs[reg] = result;
+            },
+            Opcode::DAA => self.daa(),
+            Opcode::CPL => {
+                let result = !self.regs[Reg::A];
+                self.regs[Reg::A] = result;
+                self.set_half_carry(true);
+                self.set_add_subtract(true);
+                self.set_undocumented(result);
+            },
+            Opcode::NEG => {
+                let value = self.regs[Reg::A];
+                self.regs[Reg::A] = 0;
+                self.sub_a(value, false);
+            },
+            Opcode::CCF => {
+                let carry = self.get_carry();
+                self.set_half_carry(carry);
+                self.set_add_subtract(false);
+                self.set_carry(!carry);
+                let a = self.regs[Reg::A];
+                self.set_undocumented(a);
+            },
+            Opcode::SCF => {
+                self.set_half_carry(false);
+                self.set_add_subtract(false);
+                self.set_carry(true);
+                let a = self.regs[Reg::A];
+                self.set_undocumented(a);
+            },
+            Opcode::ADDHLSS(big_reg) => {
+                let hl = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_big_reg(big_reg);
+                let result = self.add_u16(hl, value);
+                self.set_reg_pair(Reg::H, Reg::L, result);
+            },
+            Opcode::ADCHLSS(big_reg) => {
+                let value = self.get_big_reg(big_reg);
+                self.adc_hl(value);
+            },
+            Opcode::SBCHLSS(big_reg) => {
+                let value = self.get_big_reg(big_reg);
+                self.sbc_hl(value);
+            },
+            Opcode::ADDIXPP(big_reg) => {
+                let value = self.get_big_reg(big_reg);
+                self.ix = self.add_u16(self.ix, value);
+            },
+            Opcode::ADDIYRR(big_reg) => {
+                let value = self.get_big_reg(big_reg);
+                self.iy = self.add_u16(self.iy, value);
+            },
+            Opcode::INCSS(big_reg) => {
+                let value = self.get_big_reg(big_reg).wrapping_add(1);
+                self.set_big_reg(big_reg, value);
+            },
+            Opcode::INCIX => self.ix = self.ix.wrapping_add(1),
+            Opcode::INCIY => self.iy = self.iy.wrapping_add(1),
+            Opcode::DECSS(big_reg) => {
+                let value = self.get_big_reg(big_reg).wrapping_sub(1);
+                self.set_big_reg(big_reg, value);
+            },
+            Opcode::DECIX => self.ix = self.ix.wrapping_sub(1),
+            Opcode::DECIY => self.iy = self.iy.wrapping_sub(1),
+            Opcode::JPNN(address) => {
+                self.pc = address;
+                self.memptr = address;
+            },
+            Opcode::JPCCNN(condition, address) => {
+                if self.condition(condition) { self.pc = address; }
+                self.memptr = address;
+            },
+            Opcode::JRE(displacement) => self.jump_relative(displacement),
+            Opcode::JRCE(displacement) => {
+                let carry = self.get_carry();
+                self.jump_relative_if(carry, displacement);
+            },
+            Opcode::JRNCE(displacement) => {
+                let carry = self.get_carry();
+                self.jump_relative_if(!carry, displacement);
+            },
+            Opcode::JRZE(displacement) => {
+                let zero = self.get_zero();
+                self.jump_relative_if(zero, displacement);
+            },
+            Opcode::JRNZE(displacement) => {
+                let zero = self.get_zero();
+                self.jump_relative_if(!zero, displacement);
+            },
+            Opcode::JPHL => self.pc = self.get_reg_pair(Reg::H, Reg::L),
+            Opcode::JPIX => self.pc = self.ix,
+            Opcode::JPIY => self.pc = self.iy,
+            Opcode::DJNZE(displacement) => {
+                self.regs[Reg::B] = self.regs[Reg::B].wrapping_sub(1);
+                let b = self.regs[Reg::B];
+                self.jump_relative_if(b != 0, displacement);
+            },
+            Opcode::CALLNN(address) => self.call(address),
+            Opcode::CALLCCNN(condition, address) => {
+                if self.condition(condition) {
+                    self.call(address);
+                    self.cycles += 7;
+                }
+                self.memptr = address;
+            },
+            Opcode::RET => self.ret(),
+            Opcode::RETCC(condition) => {
+                let taken = self.condition(condition);
+                if taken {
+                    self.ret();
+                    self.cycles += 6;
+                }
+            },
+            Opcode::RETI | Opcode::RETN => {
+                // RETI restores IFF1 just like RETN does
+                self.iff1 = self.iff2;
+                self.ret();
+            },
+            Opcode::RETP(address) => self.call(address as u16),
+            Opcode::HALT => self.halted = true,
+            Opcode::DI => {
+                self.iff1 = false;
+                self.iff2 = false;
+            },
+            Opcode::EI => {
+                self.iff1 = true;
+                self.iff2 = true;
+                self.ei_delay = true;
+            },
+            Opcode::IM0 => self.im = 0,
+            Opcode::IM1 => self.im = 1,
+            Opcode::IM2 => self.im = 2,
+            Opcode::INAN(port) => {
+                let port = ((self.regs[Reg::A] as u16) << 8) + port as u16;
+                self.regs[Reg::A] = self.io.input(port);
+                self.memptr = port.wrapping_add(1);
+            },
+            Opcode::INRC(reg) => {
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                let value = self.io.input(port);
+                self.memptr = port.wrapping_add(1);
+                self.regs[reg] = value;
+                self.set_sign_zero_parity(value);
+                self.set_half_carry(false);
+                self.set_add_subtract(false);
+            },
+            Opcode::INFC => {
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                let value = self.io.input(port);
+                self.memptr = port.wrapping_add(1);
+                self.set_sign_zero_parity(value);
+                self.set_half_carry(false);
+                self.set_add_subtract(false);
+            },
+            Opcode::OUTNA(port) => {
+                let port = ((self.regs[Reg::A] as u16) << 8) + port as u16;
+                self.io.output(port, self.regs[Reg::A]);
+                self.set_memptr_a(port);
+            },
+            Opcode::OUTCR(reg) => {
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                self.memptr = port.wrapping_add(1);
+                self.io.output(port, self.regs[reg]);
+            },
+            Opcode::OUTC0 => {
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                self.memptr = port.wrapping_add(1);
+                self.io.output(port, 0);
+            },
+            Opcode::INI | Opcode::INIR | Opcode::IND | Opcode::INDR => {
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                let value = self.io.input(port);
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                if op == Opcode::INI || op == Opcode::INIR {
+                    self.memptr = port.wrapping_add(1);
+                } else {
+                    self.memptr = port.wrapping_sub(1);
+                }
+                self.set_mem(address, value);
+                self.regs[Reg::B] = self.regs[Reg::B].wrapping_sub(1);
+
+                let increment = op == Opcode::INI || op == Opcode::INIR;
+                let c = if increment {
+                    self.set_reg_pair(Reg::H, Reg::L, address.wrapping_add(1));
+                    self.regs[Reg::C].wrapping_add(1)
+                } else {
+                    self.set_reg_pair(Reg::H, Reg::L, address.wrapping_sub(1));
+                    self.regs[Reg::C].wrapping_sub(1)
+                };
+                let k = value as u16 + c as u16;
+                self.block_io(value, k, op == Opcode::INIR || op == Opcode::INDR);
+            },
+            Opcode::OUTI | Opcode::OTIR | Opcode::OUTD | Opcode::OTDR => {
+                self.regs[Reg::B] = self.regs[Reg::B].wrapping_sub(1);
+                let address = self.get_reg_pair(Reg::H, Reg::L);
+                let value = self.get_mem(address);
+                let port = self.get_reg_pair(Reg::B, Reg::C);
+                self.io.output(port, value);
+
+                if op == Opcode::OUTI || op == Opcode::OTIR {
+                    self.set_reg_pair(Reg::H, Reg::L, address.wrapping_add(1));
+                    self.memptr = port.wrapping_add(1);
+                } else {
+                    self.set_reg_pair(Reg::H, Reg::L, address.wrapping_sub(1));
+                    self.memptr = port.wrapping_sub(1);
+                }
+                let k = value as u16 + self.regs[Reg::L] as u16;
+                self.block_io(value, k, op == Opcode::OTIR || op == Opcode::OTDR);
             },
             _ => ()
         }
     }
 }
+
+fn is_prefix(byte: u8) -> bool {
+    byte == 0xCB || byte == 0xDD || byte == 0xED || byte == 0xFD
+}
+
+fn mask(bit: Bit) -> u8 {
+    1 << (bit & 0b111)
+}
+
+fn xorshift(state: &mut u32) -> u32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state
+}
+
+fn parity(value: u8) -> bool {
+    value.count_ones() & 1 == 0
+}
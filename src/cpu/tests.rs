@@ -1,7 +1,12 @@
 #![cfg(test)]
 
 use cpu::Z80;
+use cpu::PowerOn;
+use cpu::xorshift;
+use cpu::bus::IoBus;
+use cpu::bus::MemoryBus;
 use ops::opcodes::Opcode;
+use ops::opcodes::Condition;
 use ops::opcodes::Reg;
 use ops::opcodes::BigReg;
 
@@ -503,7 +508,6 @@ fn test_run_exspiy() {
 
 #[test]
 fn test_run_ldi() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
@@ -524,13 +528,15 @@ fn test_run_ldi() {
     assert_eq!(cpu.regs[Reg::E], 0x23);
     assert_eq!(cpu.regs[Reg::B], 0x00);
     assert_eq!(cpu.regs[Reg::C], 0x06);
-    assert_eq!(cpu.regs[Reg::F], 0b11101001);
+    assert_eq!(cpu.regs[Reg::F], 0b11001101);
 }
 
 #[test]
 fn test_run_ldir() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
+    cpu.mem[0x0100] = 0xED;
+    cpu.mem[0x0101] = 0xB0;
+    cpu.pc = 0x0100;
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
     cpu.mem[0x1111] = 0x88;
@@ -545,7 +551,14 @@ fn test_run_ldir() {
     cpu.regs[Reg::C] = 0x03;
     cpu.regs[Reg::F] = 0b11111111;
 
-    cpu.run_op(Opcode::LDIR);
+    assert_eq!(cpu.step(), (Opcode::LDIR, 21));
+    assert_eq!(cpu.pc, 0x0100);
+    assert_eq!(cpu.regs[Reg::C], 0x02);
+    assert_eq!(cpu.mem[0x2222], 0x88);
+    assert_eq!(cpu.mem[0x2223], 0x59);
+    assert_eq!(cpu.regs[Reg::F], 0b11000101);
+
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0102), 37);
     assert_eq!(cpu.regs[Reg::H], 0x11);
     assert_eq!(cpu.regs[Reg::L], 0x14);
     assert_eq!(cpu.regs[Reg::D], 0x22);
@@ -563,7 +576,6 @@ fn test_run_ldir() {
 
 #[test]
 fn test_run_ldd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
@@ -583,12 +595,39 @@ fn test_run_ldd() {
     assert_eq!(cpu.regs[Reg::E], 0x21);
     assert_eq!(cpu.regs[Reg::B], 0x00);
     assert_eq!(cpu.regs[Reg::C], 0x06);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
+}
+
+#[test]
+fn test_run_ldd_last_byte() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x11;
+    cpu.regs[Reg::L] = 0x11;
+    cpu.mem[0x1111] = 0x88;
+    cpu.regs[Reg::D] = 0x22;
+    cpu.regs[Reg::E] = 0x22;
+    cpu.mem[0x2222] = 0x66;
+    cpu.regs[Reg::A] = 0x02;
+    cpu.regs[Reg::B] = 0x00;
+    cpu.regs[Reg::C] = 0x01;
+
+    cpu.run_op(Opcode::LDD);
+    assert_eq!(cpu.mem[0x1111], 0x88);
+    assert_eq!(cpu.mem[0x2222], 0x88);
+    assert_eq!(cpu.regs[Reg::H], 0x11);
+    assert_eq!(cpu.regs[Reg::L], 0x10);
+    assert_eq!(cpu.regs[Reg::D], 0x22);
+    assert_eq!(cpu.regs[Reg::E], 0x21);
+    assert_eq!(cpu.regs[Reg::B], 0x00);
+    assert_eq!(cpu.regs[Reg::C], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b00101000);
 }
 
 #[test]
 fn test_run_lddr() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0xB8;
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x14;
     cpu.mem[0x1112] = 0x88;
@@ -602,7 +641,7 @@ fn test_run_lddr() {
     cpu.regs[Reg::B] = 0x00;
     cpu.regs[Reg::C] = 0x03;
 
-    cpu.run_op(Opcode::LDDR);
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0002), 58);
     assert_eq!(cpu.regs[Reg::H], 0x11);
     assert_eq!(cpu.regs[Reg::L], 0x11);
     assert_eq!(cpu.regs[Reg::D], 0x22);
@@ -615,11 +654,11 @@ fn test_run_lddr() {
     assert_eq!(cpu.mem[0x2223], 0x88);
     assert_eq!(cpu.mem[0x2224], 0x36);
     assert_eq!(cpu.mem[0x2225], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
 }
 
 #[test]
 fn test_run_cpi() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
@@ -627,6 +666,7 @@ fn test_run_cpi() {
     cpu.regs[Reg::A] = 0x3B;
     cpu.regs[Reg::B] = 0x00;
     cpu.regs[Reg::C] = 0x01;
+    cpu.regs[Reg::F] = 0b00000001;
 
     cpu.run_op(Opcode::CPI);
     assert_eq!(cpu.mem[0x1111], 0x3B);
@@ -635,12 +675,14 @@ fn test_run_cpi() {
     assert_eq!(cpu.regs[Reg::A], 0x3B);
     assert_eq!(cpu.regs[Reg::B], 0x00);
     assert_eq!(cpu.regs[Reg::C], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000011);
 }
 
 #[test]
 fn test_run_cpir() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0xB1;
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
     cpu.regs[Reg::A] = 0xF3;
@@ -650,7 +692,11 @@ fn test_run_cpir() {
     cpu.regs[Reg::B] = 0x00;
     cpu.regs[Reg::C] = 0x07;
 
-    cpu.run_op(Opcode::CPIR);
+    assert_eq!(cpu.step(), (Opcode::CPIR, 21));
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.regs[Reg::F], 0b10000110);
+
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0002), 37);
     assert_eq!(cpu.regs[Reg::H], 0x11);
     assert_eq!(cpu.regs[Reg::L], 0x14);
     assert_eq!(cpu.regs[Reg::A], 0xF3);
@@ -659,11 +705,11 @@ fn test_run_cpir() {
     assert_eq!(cpu.mem[0x1111], 0x52);
     assert_eq!(cpu.mem[0x1112], 0x00);
     assert_eq!(cpu.mem[0x1113], 0xF3);
+    assert_eq!(cpu.regs[Reg::F], 0b01000110);
 }
 
 #[test]
 fn test_run_cpd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x11;
@@ -679,12 +725,34 @@ fn test_run_cpd() {
     assert_eq!(cpu.regs[Reg::A], 0x3B);
     assert_eq!(cpu.regs[Reg::B], 0x00);
     assert_eq!(cpu.regs[Reg::C], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000010);
+}
+
+#[test]
+fn test_run_cpd_no_match() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x11;
+    cpu.regs[Reg::L] = 0x11;
+    cpu.mem[0x1111] = 0x3C;
+    cpu.regs[Reg::A] = 0x3B;
+    cpu.regs[Reg::B] = 0x00;
+    cpu.regs[Reg::C] = 0x02;
+
+    cpu.run_op(Opcode::CPD);
+    assert_eq!(cpu.mem[0x1111], 0x3C);
+    assert_eq!(cpu.regs[Reg::H], 0x11);
+    assert_eq!(cpu.regs[Reg::L], 0x10);
+    assert_eq!(cpu.regs[Reg::A], 0x3B);
+    assert_eq!(cpu.regs[Reg::B], 0x00);
+    assert_eq!(cpu.regs[Reg::C], 0x01);
+    assert_eq!(cpu.regs[Reg::F], 0b10111110);
 }
 
 #[test]
 fn test_run_cpdr() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0xB9;
     cpu.regs[Reg::H] = 0x11;
     cpu.regs[Reg::L] = 0x18;
     cpu.regs[Reg::A] = 0xF3;
@@ -694,7 +762,7 @@ fn test_run_cpdr() {
     cpu.regs[Reg::B] = 0x00;
     cpu.regs[Reg::C] = 0x07;
 
-    cpu.run_op(Opcode::CPDR);
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0002), 58);
     assert_eq!(cpu.regs[Reg::H], 0x11);
     assert_eq!(cpu.regs[Reg::L], 0x15);
     assert_eq!(cpu.regs[Reg::A], 0xF3);
@@ -703,31 +771,31 @@ fn test_run_cpdr() {
     assert_eq!(cpu.mem[0x1118], 0x52);
     assert_eq!(cpu.mem[0x1117], 0x00);
     assert_eq!(cpu.mem[0x1116], 0xF3);
+    assert_eq!(cpu.regs[Reg::F], 0b01000110);
 }
 
 #[test]
 fn test_run_addar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.regs[Reg::B] = 0x11;
     cpu.run_op(Opcode::ADDAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0x55);
+    assert_eq!(cpu.regs[Reg::F], 0b00000000);
     assert_eq!(cpu.regs[Reg::B], 0x11);
 }
 
 #[test]
 fn test_run_addan() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.run_op(Opcode::ADDAN(0x11));
     assert_eq!(cpu.regs[Reg::A], 0x55);
+    assert_eq!(cpu.regs[Reg::F], 0b00000000);
 }
 
 #[test]
 fn test_run_addahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xA0;
     cpu.regs[Reg::H] = 0x23;
@@ -735,53 +803,111 @@ fn test_run_addahl() {
     cpu.mem[0x2323] = 0x08;
     cpu.run_op(Opcode::ADDAHL);
     assert_eq!(cpu.regs[Reg::A], 0xA8);
+    assert_eq!(cpu.regs[Reg::F], 0b10101000);
 }
 
 #[test]
 fn test_run_addaixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x11;
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x22;
     cpu.run_op(Opcode::ADDAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x33);
+    assert_eq!(cpu.regs[Reg::F], 0b00100000);
 }
 
 #[test]
 fn test_run_addaiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x11;
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x22;
     cpu.run_op(Opcode::ADDAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x33);
+    assert_eq!(cpu.regs[Reg::F], 0b00100000);
+}
+
+#[test]
+fn test_run_adcar() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x16;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::B] = 0x10;
+    cpu.run_op(Opcode::ADCAR(Reg::B));
+    assert_eq!(cpu.regs[Reg::A], 0x27);
+    assert_eq!(cpu.regs[Reg::F], 0b00100000);
+}
+
+#[test]
+fn test_run_adcan() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x0F;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.run_op(Opcode::ADCAN(0x00));
+    assert_eq!(cpu.regs[Reg::A], 0x10);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
+}
+
+#[test]
+fn test_run_adcahl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x7F;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::H] = 0x66;
+    cpu.regs[Reg::L] = 0x66;
+    cpu.mem[0x6666] = 0x00;
+    cpu.run_op(Opcode::ADCAHL);
+    assert_eq!(cpu.regs[Reg::A], 0x80);
+    assert_eq!(cpu.regs[Reg::F], 0b10010100);
+}
+
+#[test]
+fn test_run_adcaixd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0xFF;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.ix = 0x1000;
+    cpu.mem[0x1005] = 0x00;
+    cpu.run_op(Opcode::ADCAIXD(0x5));
+    assert_eq!(cpu.regs[Reg::A], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01010001);
+}
+
+#[test]
+fn test_run_adcaiyd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x3A;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.iy = 0x1000;
+    cpu.mem[0x1005] = 0xC5;
+    cpu.run_op(Opcode::ADCAIYD(0x5));
+    assert_eq!(cpu.regs[Reg::A], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01010001);
 }
 
 #[test]
 fn test_run_subar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.regs[Reg::B] = 0x11;
     cpu.run_op(Opcode::SUBAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0x33);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
     assert_eq!(cpu.regs[Reg::B], 0x11);
 }
 
 #[test]
 fn test_run_suban() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.run_op(Opcode::SUBAN(0x11));
     assert_eq!(cpu.regs[Reg::A], 0x33);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_subahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xA8;
     cpu.regs[Reg::H] = 0x23;
@@ -789,55 +915,55 @@ fn test_run_subahl() {
     cpu.mem[0x2323] = 0x08;
     cpu.run_op(Opcode::SUBAHL);
     assert_eq!(cpu.regs[Reg::A], 0xA0);
+    assert_eq!(cpu.regs[Reg::F], 0b10100010);
 }
 
 #[test]
 fn test_run_subaixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x33;
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x11;
     cpu.run_op(Opcode::SUBAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x22);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_subaiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x33;
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x11;
     cpu.run_op(Opcode::SUBAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x22);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_sbcar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.regs[Reg::F] = 0b00000001;
     cpu.regs[Reg::B] = 0x11;
     cpu.run_op(Opcode::SBCAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0x32);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
     assert_eq!(cpu.regs[Reg::B], 0x11);
 }
 
 #[test]
 fn test_run_sbcan() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x44;
     cpu.regs[Reg::F] = 0b00000001;
     cpu.run_op(Opcode::SBCAN(0x11));
     assert_eq!(cpu.regs[Reg::A], 0x32);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_sbcahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xA8;
     cpu.regs[Reg::F] = 0b00000001;
@@ -846,11 +972,11 @@ fn test_run_sbcahl() {
     cpu.mem[0x2323] = 0x08;
     cpu.run_op(Opcode::SBCAHL);
     assert_eq!(cpu.regs[Reg::A], 0x9F);
+    assert_eq!(cpu.regs[Reg::F], 0b10011010);
 }
 
 #[test]
 fn test_run_sbcaixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x33;
     cpu.regs[Reg::F] = 0b00000001;
@@ -858,11 +984,11 @@ fn test_run_sbcaixd() {
     cpu.mem[0x1005] = 0x11;
     cpu.run_op(Opcode::SBCAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x21);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_sbcaiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x33;
     cpu.regs[Reg::F] = 0b00000001;
@@ -870,30 +996,30 @@ fn test_run_sbcaiyd() {
     cpu.mem[0x1005] = 0x11;
     cpu.run_op(Opcode::SBCAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x21);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
 }
 
 #[test]
 fn test_run_andar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xC3;
     cpu.regs[Reg::B] = 0x7B;
     cpu.run_op(Opcode::ANDAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0x43);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
 }
 
 #[test]
 fn test_run_andan() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xC3;
     cpu.run_op(Opcode::ANDAN(0x7B));
     assert_eq!(cpu.regs[Reg::A], 0x43);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
 }
 
 #[test]
 fn test_run_andahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xC3;
     cpu.regs[Reg::H] = 0x23;
@@ -901,52 +1027,52 @@ fn test_run_andahl() {
     cpu.mem[0x2323] = 0x7B;
     cpu.run_op(Opcode::ANDAHL);
     assert_eq!(cpu.regs[Reg::A], 0x43);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
 }
 
 #[test]
 fn test_run_andaixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xC3;
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x7B;
     cpu.run_op(Opcode::ANDAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x43);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
 }
 
 #[test]
 fn test_run_andaiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0xC3;
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x7B;
     cpu.run_op(Opcode::ANDAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x43);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
 }
 
 #[test]
 fn test_run_orar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x12;
     cpu.regs[Reg::B] = 0x48;
     cpu.run_op(Opcode::ORAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
 }
 
 #[test]
 fn test_run_oran() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x12;
     cpu.run_op(Opcode::ORAN(0x48));
     assert_eq!(cpu.regs[Reg::A], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
 }
 
 #[test]
 fn test_run_orahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x12;
     cpu.regs[Reg::H] = 0x23;
@@ -954,52 +1080,52 @@ fn test_run_orahl() {
     cpu.mem[0x2323] = 0x48;
     cpu.run_op(Opcode::ORAHL);
     assert_eq!(cpu.regs[Reg::A], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
 }
 
 #[test]
 fn test_run_oraixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x12;
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x48;
     cpu.run_op(Opcode::ORAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
 }
 
 #[test]
 fn test_run_oraiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x12;
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x48;
     cpu.run_op(Opcode::ORAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0x5A);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
 }
 
 #[test]
 fn test_run_xorar() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x96;
     cpu.regs[Reg::B] = 0x5D;
     cpu.run_op(Opcode::XORAR(Reg::B));
     assert_eq!(cpu.regs[Reg::A], 0xCB);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
 }
 
 #[test]
 fn test_run_xoran() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x96;
     cpu.run_op(Opcode::XORAN(0x5D));
     assert_eq!(cpu.regs[Reg::A], 0xCB);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
 }
 
 #[test]
 fn test_run_xorahl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x96;
     cpu.regs[Reg::H] = 0x23;
@@ -1007,106 +1133,2146 @@ fn test_run_xorahl() {
     cpu.mem[0x2323] = 0x5D;
     cpu.run_op(Opcode::XORAHL);
     assert_eq!(cpu.regs[Reg::A], 0xCB);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
 }
 
 #[test]
 fn test_run_xoraixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x96;
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::XORAIXD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0xCB);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
 }
 
 #[test]
 fn test_run_xoraiyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::A] = 0x96;
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::XORAIYD(0x5));
     assert_eq!(cpu.regs[Reg::A], 0xCB);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
 }
 
 #[test]
 fn test_run_incr() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::B] = 0x5D;
     cpu.run_op(Opcode::INCR(Reg::B));
     assert_eq!(cpu.regs[Reg::B], 0x5E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
 }
 
 #[test]
 fn test_run_inchl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x23;
     cpu.regs[Reg::L] = 0x23;
     cpu.mem[0x2323] = 0x5D;
     cpu.run_op(Opcode::INCHL);
     assert_eq!(cpu.mem[0x2323], 0x5E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
 }
 
 #[test]
 fn test_run_incixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::INCIXD(0x5));
     assert_eq!(cpu.mem[0x1005], 0x5E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
 }
 
 #[test]
 fn test_run_inciyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::INCIYD(0x5));
     assert_eq!(cpu.mem[0x1005], 0x5E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
 }
 
 #[test]
 fn test_run_decr() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::B] = 0x5D;
     cpu.run_op(Opcode::DECR(Reg::B));
     assert_eq!(cpu.regs[Reg::B], 0x5C);
+    assert_eq!(cpu.regs[Reg::F], 0b00001010);
 }
 
 #[test]
 fn test_run_dechl() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.regs[Reg::H] = 0x23;
     cpu.regs[Reg::L] = 0x23;
     cpu.mem[0x2323] = 0x5D;
     cpu.run_op(Opcode::DECHL);
     assert_eq!(cpu.mem[0x2323], 0x5C);
+    assert_eq!(cpu.regs[Reg::F], 0b00001010);
 }
 
 #[test]
 fn test_run_decixd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.ix = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::DECIXD(0x5));
     assert_eq!(cpu.mem[0x1005], 0x5C);
+    assert_eq!(cpu.regs[Reg::F], 0b00001010);
 }
 
 #[test]
 fn test_run_deciyd() {
-    // TODO: Review the "Condition Bits Affected" from z80 user manual
     let mut cpu = Z80::new();
     cpu.iy = 0x1000;
     cpu.mem[0x1005] = 0x5D;
     cpu.run_op(Opcode::DECIYD(0x5));
     assert_eq!(cpu.mem[0x1005], 0x5C);
+    assert_eq!(cpu.regs[Reg::F], 0b00001010);
+}
+
+#[test]
+fn test_register_accessors() {
+    let mut cpu = Z80::new();
+    cpu.set_reg(Reg::A2, 0x12);
+    cpu.set_big_reg(BigReg::BC, 0x3456);
+    cpu.set_i(0x78);
+    cpu.set_r(0x9A);
+    cpu.set_ix(0xBCDE);
+    cpu.set_iy(0xF012);
+    cpu.set_sp(0x3456);
+    cpu.set_pc(0x789A);
+    cpu.set_iff1(true);
+    cpu.set_iff2(true);
+    cpu.set_mem(0xFFFF, 0x42);
+    assert_eq!(cpu.get_reg(Reg::A2), 0x12);
+    assert_eq!(cpu.get_reg(Reg::B), 0x34);
+    assert_eq!(cpu.get_reg(Reg::C), 0x56);
+    assert_eq!(cpu.get_i(), 0x78);
+    assert_eq!(cpu.get_r(), 0x9A);
+    assert_eq!(cpu.get_ix(), 0xBCDE);
+    assert_eq!(cpu.get_iy(), 0xF012);
+    assert_eq!(cpu.get_sp(), 0x3456);
+    assert_eq!(cpu.get_pc(), 0x789A);
+    assert!(cpu.get_iff1());
+    assert!(cpu.get_iff2());
+    assert_eq!(cpu.get_mem(0xFFFF), 0x42);
+}
+
+#[test]
+fn test_flag_accessors() {
+    let mut cpu = Z80::new();
+    cpu.set_sign(true);
+    cpu.set_half_carry(true);
+    cpu.set_carry(true);
+    assert_eq!(cpu.get_reg(Reg::F), 0b10010001);
+    assert!(cpu.get_sign());
+    assert!(!cpu.get_zero());
+    assert!(cpu.get_half_carry());
+    assert!(!cpu.get_parity_overflow());
+    assert!(!cpu.get_add_subtract());
+    assert!(cpu.get_carry());
+}
+
+#[test]
+fn test_step() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0x3E;
+    cpu.mem[0x0001] = 0x05;
+    cpu.mem[0x0002] = 0x47;
+    assert_eq!(cpu.step(), (Opcode::LDRN(Reg::A, 0x05), 7));
+    assert_eq!(cpu.pc, 0x0002);
+    assert_eq!(cpu.step(), (Opcode::LDRR(Reg::B, Reg::A), 4));
+    assert_eq!(cpu.pc, 0x0003);
+    assert_eq!(cpu.regs[Reg::B], 0x05);
+    assert_eq!(cpu.get_cycles(), 11);
+}
+
+#[test]
+fn test_step_wraps_pc() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0xFFFF;
+    cpu.mem[0xFFFF] = 0x3E;
+    cpu.mem[0x0000] = 0x07;
+    assert_eq!(cpu.step(), (Opcode::LDRN(Reg::A, 0x07), 7));
+    assert_eq!(cpu.pc, 0x0001);
+    assert_eq!(cpu.regs[Reg::A], 0x07);
+}
+
+#[test]
+fn test_step_undefined_ed_timing() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0x00;
+    assert_eq!(cpu.step(), (Opcode::NOP, 8));
+    assert_eq!(cpu.pc, 0x0002);
+    assert_eq!(cpu.r, 0x02);
+}
+
+#[test]
+fn test_step_prefixed_halt_timing() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xDD;
+    cpu.mem[0x0001] = 0x76;
+    assert_eq!(cpu.step(), (Opcode::HALT, 8));
+    assert_eq!(cpu.pc, 0x0002);
+    assert!(cpu.get_halted());
+}
+
+#[test]
+fn test_run_for_cycles() {
+    let mut cpu = Z80::new();
+    for address in 0..6 {
+        cpu.mem[address] = 0x47;
+    }
+    assert_eq!(cpu.run_for_cycles(10), 12);
+    assert_eq!(cpu.pc, 0x0003);
+}
+
+#[test]
+fn test_run_until() {
+    let mut cpu = Z80::new();
+    for address in 0..6 {
+        cpu.mem[address] = 0x47;
+    }
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 5), 20);
+    assert_eq!(cpu.pc, 0x0005);
+}
+
+struct RomBus {
+    data: Vec<u8>,
+    opcode_fetches: Vec<u16>,
+}
+
+impl MemoryBus for RomBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address >= 0x4000 {
+            self.data[address as usize] = value;
+        }
+    }
+
+    fn fetch_opcode(&mut self, address: u16) -> u8 {
+        self.opcode_fetches.push(address);
+        self.read(address)
+    }
+}
+
+#[test]
+fn test_custom_memory_bus() {
+    let mut data = vec![0; 65536];
+    data[0x0000] = 0x32;
+    data[0x0001] = 0x10;
+    data[0x0002] = 0x10;
+    data[0x0003] = 0xDD;
+    data[0x0004] = 0x77;
+    data[0x0005] = 0x00;
+    let mut cpu = Z80::with_memory(RomBus { data, opcode_fetches: vec![] });
+    cpu.regs[Reg::A] = 0x55;
+    cpu.ix = 0x4000;
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.get_memory().data[0x1010], 0x00);
+    assert_eq!(cpu.get_memory().data[0x4000], 0x55);
+    assert_eq!(cpu.get_memory().opcode_fetches, vec![0x0000, 0x0003, 0x0004]);
+}
+
+#[test]
+fn test_unused_prefix_fetches_follower_once() {
+    let mut data = vec![0; 65536];
+    data[0x0000] = 0xDD;
+    data[0x0001] = 0x01;
+    data[0x0002] = 0x34;
+    data[0x0003] = 0x12;
+    let mut cpu = Z80::with_memory(RomBus { data, opcode_fetches: vec![] });
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.step(), (Opcode::LDDDNN(BigReg::BC, 0x1234), 10));
+    assert_eq!(cpu.pc, 0x0004);
+    assert_eq!(cpu.r, 0x02);
+    assert_eq!(cpu.get_memory().opcode_fetches, vec![0x0000, 0x0001]);
+}
+
+#[test]
+fn test_no_interrupt_after_unused_prefix() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xFD;
+    cpu.mem[0x0001] = 0x00;
+    cpu.im = 1;
+    cpu.iff1 = true;
+    cpu.step();
+    cpu.assert_int(0xFF);
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.pc, 0x0002);
+    assert_eq!(cpu.step(), (Opcode::RETP(0x38), 13));
+}
+
+struct TestIo {
+    input: u8,
+    reads: Vec<u16>,
+    writes: Vec<(u16, u8)>,
+}
+
+impl IoBus for TestIo {
+    fn input(&mut self, port: u16) -> u8 {
+        self.reads.push(port);
+        self.input
+    }
+
+    fn output(&mut self, port: u16, value: u8) {
+        self.writes.push((port, value));
+    }
+}
+
+fn io_cpu(input: u8) -> Z80<[u8; 65536], TestIo> {
+    Z80::with_bus([0; 65536], TestIo { input, reads: vec![], writes: vec![] })
+}
+
+#[test]
+fn test_run_inan() {
+    let mut cpu = io_cpu(0x7B);
+    cpu.regs[Reg::A] = 0x23;
+    cpu.regs[Reg::F] = 0b11111111;
+    cpu.run_op(Opcode::INAN(0x01));
+    assert_eq!(cpu.regs[Reg::A], 0x7B);
+    assert_eq!(cpu.regs[Reg::F], 0b11111111);
+    assert_eq!(cpu.get_io().reads, vec![0x2301]);
+}
+
+#[test]
+fn test_run_inrc() {
+    let mut cpu = io_cpu(0x7B);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::F] = 0b00010011;
+    cpu.run_op(Opcode::INRC(Reg::D));
+    assert_eq!(cpu.regs[Reg::D], 0x7B);
+    assert_eq!(cpu.regs[Reg::F], 0b00101101);
+    assert_eq!(cpu.get_io().reads, vec![0x1007]);
+}
+
+#[test]
+fn test_run_infc() {
+    let mut cpu = io_cpu(0x00);
+    cpu.regs[Reg::A] = 0x55;
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.run_op(Opcode::INFC);
+    assert_eq!(cpu.regs[Reg::A], 0x55);
+    assert_eq!(cpu.regs[Reg::F], 0b01000100);
+    assert_eq!(cpu.get_io().reads, vec![0x1007]);
+}
+
+#[test]
+fn test_run_outna() {
+    let mut cpu = io_cpu(0);
+    cpu.regs[Reg::A] = 0x23;
+    cpu.run_op(Opcode::OUTNA(0x01));
+    assert_eq!(cpu.get_io().writes, vec![(0x2301, 0x23)]);
+}
+
+#[test]
+fn test_run_outcr() {
+    let mut cpu = io_cpu(0);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::D] = 0x5A;
+    cpu.run_op(Opcode::OUTCR(Reg::D));
+    assert_eq!(cpu.get_io().writes, vec![(0x1007, 0x5A)]);
+}
+
+#[test]
+fn test_run_outc0() {
+    let mut cpu = io_cpu(0);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::F] = 0x5A;
+    cpu.run_op(Opcode::OUTC0);
+    assert_eq!(cpu.get_io().writes, vec![(0x1007, 0x00)]);
+}
+
+#[test]
+fn test_run_ini() {
+    let mut cpu = io_cpu(0x7B);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.run_op(Opcode::INI);
+    assert_eq!(cpu.mem[0x1000], 0x7B);
+    assert_eq!(cpu.regs[Reg::H], 0x10);
+    assert_eq!(cpu.regs[Reg::L], 0x01);
+    assert_eq!(cpu.regs[Reg::B], 0x0F);
+    assert_eq!(cpu.regs[Reg::F], 0b00001100);
+    assert_eq!(cpu.get_io().reads, vec![0x1007]);
+}
+
+#[test]
+fn test_run_inir() {
+    let mut cpu = io_cpu(0x7B);
+    cpu.pc = 0x2A02;
+    cpu.regs[Reg::B] = 0x02;
+    cpu.regs[Reg::C] = 0xFF;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.run_op(Opcode::INIR);
+    assert_eq!(cpu.mem[0x1000], 0x7B);
+    assert_eq!(cpu.regs[Reg::L], 0x01);
+    assert_eq!(cpu.regs[Reg::B], 0x01);
+    assert_eq!(cpu.regs[Reg::F], 0b00101100);
+    assert_eq!(cpu.pc, 0x2A00);
+    assert_eq!(cpu.get_cycles(), 21);
+
+    cpu.pc = 0x2A02;
+    cpu.run_op(Opcode::INIR);
+    assert_eq!(cpu.mem[0x1001], 0x7B);
+    assert_eq!(cpu.regs[Reg::B], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000100);
+    assert_eq!(cpu.pc, 0x2A02);
+    assert_eq!(cpu.get_cycles(), 37);
+
+    let mut cpu = io_cpu(0x90);
+    cpu.pc = 0x0102;
+    cpu.regs[Reg::B] = 0x11;
+    cpu.regs[Reg::C] = 0x80;
+    cpu.run_op(Opcode::INIR);
+    assert_eq!(cpu.regs[Reg::B], 0x10);
+    assert_eq!(cpu.regs[Reg::F], 0b00010011);
+    assert_eq!(cpu.pc, 0x0100);
+}
+
+#[test]
+fn test_run_ind() {
+    let mut cpu = io_cpu(0xFB);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.run_op(Opcode::IND);
+    assert_eq!(cpu.mem[0x1000], 0xFB);
+    assert_eq!(cpu.regs[Reg::H], 0x0F);
+    assert_eq!(cpu.regs[Reg::L], 0xFF);
+    assert_eq!(cpu.regs[Reg::B], 0x0F);
+    assert_eq!(cpu.regs[Reg::F], 0b00011011);
+}
+
+#[test]
+fn test_run_indr() {
+    let mut cpu = io_cpu(0x7B);
+    cpu.pc = 0x0102;
+    cpu.regs[Reg::B] = 0x01;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.run_op(Opcode::INDR);
+    assert_eq!(cpu.mem[0x1000], 0x7B);
+    assert_eq!(cpu.regs[Reg::L], 0xFF);
+    assert_eq!(cpu.regs[Reg::B], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000000);
+    assert_eq!(cpu.pc, 0x0102);
+    assert_eq!(cpu.get_cycles(), 16);
+}
+
+#[test]
+fn test_run_outi() {
+    let mut cpu = io_cpu(0);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0xFF;
+    cpu.mem[0x10FF] = 0x59;
+    cpu.run_op(Opcode::OUTI);
+    assert_eq!(cpu.regs[Reg::H], 0x11);
+    assert_eq!(cpu.regs[Reg::L], 0x00);
+    assert_eq!(cpu.regs[Reg::B], 0x0F);
+    assert_eq!(cpu.regs[Reg::F], 0b00001000);
+    assert_eq!(cpu.get_io().writes, vec![(0x0F07, 0x59)]);
+}
+
+#[test]
+fn test_run_otir() {
+    let mut cpu = io_cpu(0);
+    cpu.pc = 0x0802;
+    cpu.regs[Reg::B] = 0x03;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x10;
+    cpu.mem[0x1010] = 0x91;
+    cpu.run_op(Opcode::OTIR);
+    assert_eq!(cpu.regs[Reg::L], 0x11);
+    assert_eq!(cpu.regs[Reg::B], 0x02);
+    assert_eq!(cpu.regs[Reg::F], 0b00001010);
+    assert_eq!(cpu.pc, 0x0800);
+    assert_eq!(cpu.get_cycles(), 21);
+    assert_eq!(cpu.get_io().writes, vec![(0x0207, 0x91)]);
+}
+
+#[test]
+fn test_run_outd() {
+    let mut cpu = io_cpu(0);
+    cpu.regs[Reg::B] = 0x10;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.mem[0x1000] = 0x80;
+    cpu.run_op(Opcode::OUTD);
+    assert_eq!(cpu.regs[Reg::H], 0x0F);
+    assert_eq!(cpu.regs[Reg::L], 0xFF);
+    assert_eq!(cpu.regs[Reg::B], 0x0F);
+    assert_eq!(cpu.regs[Reg::F], 0b00011011);
+    assert_eq!(cpu.get_io().writes, vec![(0x0F07, 0x80)]);
+}
+
+#[test]
+fn test_run_otdr() {
+    let mut cpu = io_cpu(0);
+    cpu.pc = 0x0102;
+    cpu.regs[Reg::B] = 0x01;
+    cpu.regs[Reg::C] = 0x07;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.regs[Reg::L] = 0x10;
+    cpu.mem[0x1010] = 0x91;
+    cpu.run_op(Opcode::OTDR);
+    assert_eq!(cpu.regs[Reg::L], 0x0F);
+    assert_eq!(cpu.regs[Reg::B], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000110);
+    assert_eq!(cpu.pc, 0x0102);
+    assert_eq!(cpu.get_io().writes, vec![(0x0007, 0x91)]);
+}
+
+#[test]
+fn test_step_little_endian_immediates() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0x21;
+    cpu.mem[0x0001] = 0x34;
+    cpu.mem[0x0002] = 0x12;
+    cpu.mem[0x0003] = 0x22;
+    cpu.mem[0x0004] = 0x00;
+    cpu.mem[0x0005] = 0x80;
+    cpu.step();
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x1234);
+    cpu.step();
+    assert_eq!(cpu.mem[0x8000], 0x34);
+    assert_eq!(cpu.mem[0x8001], 0x12);
+}
+
+#[test]
+fn test_run_negative_displacement() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0FFE] = 0x39;
+    cpu.ix = 0x1000;
+    cpu.run_op(Opcode::LDRIXD(Reg::A, -2));
+    assert_eq!(cpu.regs[Reg::A], 0x39);
+
+    cpu.iy = 0x0000;
+    cpu.run_op(Opcode::LDIYDR(-1, Reg::A));
+    assert_eq!(cpu.mem[0xFFFF], 0x39);
+
+    cpu.ix = 0xFFF0;
+    cpu.run_op(Opcode::LDIXDN(0x7F, 0x12));
+    assert_eq!(cpu.mem[0x006F], 0x12);
+}
+
+#[test]
+fn test_random_programs_never_panic() {
+    let mut seed = 0x2545F491;
+    for _ in 0..32 {
+        let mut cpu = Z80::new();
+        for address in 0..65536 {
+            cpu.mem[address] = xorshift(&mut seed) as u8;
+        }
+        for reg in 0..16 {
+            cpu.regs[reg] = xorshift(&mut seed) as u8;
+        }
+        cpu.i = xorshift(&mut seed) as u8;
+        cpu.r = xorshift(&mut seed) as u8;
+        cpu.ix = xorshift(&mut seed) as u16;
+        cpu.iy = xorshift(&mut seed) as u16;
+        cpu.sp = xorshift(&mut seed) as u16;
+        cpu.pc = xorshift(&mut seed) as u16;
+        cpu.run_for_cycles(50000);
+    }
+}
+
+#[test]
+fn test_run_cpar() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x63;
+    cpu.regs[Reg::B] = 0x60;
+    cpu.run_op(Opcode::CPAR(Reg::B));
+    assert_eq!(cpu.regs[Reg::A], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
+}
+
+#[test]
+fn test_run_cpan() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x10;
+    cpu.run_op(Opcode::CPAN(0x2B));
+    assert_eq!(cpu.regs[Reg::A], 0x10);
+    assert_eq!(cpu.regs[Reg::F], 0b10111011);
+}
+
+#[test]
+fn test_run_cpahl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x3B;
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x3B;
+    cpu.run_op(Opcode::CPAHL);
+    assert_eq!(cpu.regs[Reg::A], 0x3B);
+    assert_eq!(cpu.regs[Reg::F], 0b01101010);
+}
+
+#[test]
+fn test_run_cpaixd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x63;
+    cpu.ix = 0x1000;
+    cpu.mem[0x1005] = 0x60;
+    cpu.run_op(Opcode::CPAIXD(0x5));
+    assert_eq!(cpu.regs[Reg::A], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
+}
+
+#[test]
+fn test_run_cpaiyd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x63;
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFB] = 0x60;
+    cpu.run_op(Opcode::CPAIYD(-0x5));
+    assert_eq!(cpu.regs[Reg::A], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100010);
+}
+
+#[test]
+fn test_run_alu_overflow_and_carry() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x7F;
+    cpu.run_op(Opcode::ADDAN(0x01));
+    assert_eq!(cpu.regs[Reg::A], 0x80);
+    assert_eq!(cpu.regs[Reg::F], 0b10010100);
+
+    cpu.regs[Reg::A] = 0xFF;
+    cpu.run_op(Opcode::ADDAN(0x01));
+    assert_eq!(cpu.regs[Reg::A], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01010001);
+
+    cpu.regs[Reg::A] = 0x80;
+    cpu.run_op(Opcode::SUBAN(0x01));
+    assert_eq!(cpu.regs[Reg::A], 0x7F);
+    assert_eq!(cpu.regs[Reg::F], 0b00111110);
+}
+
+#[test]
+fn test_run_inc_dec_boundaries() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::B] = 0x7F;
+    cpu.run_op(Opcode::INCR(Reg::B));
+    assert_eq!(cpu.regs[Reg::B], 0x80);
+    assert_eq!(cpu.regs[Reg::F], 0b10010101);
+
+    cpu.run_op(Opcode::DECR(Reg::B));
+    assert_eq!(cpu.regs[Reg::B], 0x7F);
+    assert_eq!(cpu.regs[Reg::F], 0b00111111);
+
+    cpu.regs[Reg::C] = 0xFF;
+    cpu.run_op(Opcode::INCR(Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01010001);
+
+    cpu.regs[Reg::C] = 0x01;
+    cpu.run_op(Opcode::DECR(Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000011);
+
+    cpu.regs[Reg::F] = 0;
+    cpu.run_op(Opcode::DECR(Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0xFF);
+    assert_eq!(cpu.regs[Reg::F], 0b10111010);
+}
+
+#[test]
+fn test_run_rlcr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::B] = 0x88;
+    cpu.run_op(Opcode::RLCR(Reg::B));
+    assert_eq!(cpu.regs[Reg::B], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_rlchl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x88;
+    cpu.run_op(Opcode::RLCHL);
+    assert_eq!(cpu.mem[0x2323], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_rlcixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0x88;
+    cpu.run_op(Opcode::RLCIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_rlciyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x88;
+    cpu.run_op(Opcode::RLCIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_rrcr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::C] = 0x31;
+    cpu.run_op(Opcode::RRCR(Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0x98);
+    assert_eq!(cpu.regs[Reg::F], 0b10001001);
+}
+
+#[test]
+fn test_run_rrchl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x31;
+    cpu.run_op(Opcode::RRCHL);
+    assert_eq!(cpu.mem[0x2323], 0x98);
+    assert_eq!(cpu.regs[Reg::F], 0b10001001);
+}
+
+#[test]
+fn test_run_rrcixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0x31;
+    cpu.run_op(Opcode::RRCIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x98);
+    assert_eq!(cpu.regs[Reg::F], 0b10001001);
+}
+
+#[test]
+fn test_run_rrciyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x31;
+    cpu.run_op(Opcode::RRCIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x98);
+    assert_eq!(cpu.regs[Reg::F], 0b10001001);
+}
+
+#[test]
+fn test_run_rlr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::D] = 0x8F;
+    cpu.run_op(Opcode::RLR(Reg::D));
+    assert_eq!(cpu.regs[Reg::D], 0x1E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001101);
+}
+
+#[test]
+fn test_run_rlhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x8F;
+    cpu.run_op(Opcode::RLHL);
+    assert_eq!(cpu.mem[0x2323], 0x1E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001101);
+}
+
+#[test]
+fn test_run_rlixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0x8F;
+    cpu.run_op(Opcode::RLIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x1E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001101);
+}
+
+#[test]
+fn test_run_rliyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x8F;
+    cpu.run_op(Opcode::RLIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x1E);
+    assert_eq!(cpu.regs[Reg::F], 0b00001101);
+}
+
+#[test]
+fn test_run_rrr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::E] = 0xDD;
+    cpu.run_op(Opcode::RRR(Reg::E));
+    assert_eq!(cpu.regs[Reg::E], 0xEE);
+    assert_eq!(cpu.regs[Reg::F], 0b10101101);
+}
+
+#[test]
+fn test_run_rrhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0xDD;
+    cpu.run_op(Opcode::RRHL);
+    assert_eq!(cpu.mem[0x2323], 0xEE);
+    assert_eq!(cpu.regs[Reg::F], 0b10101101);
+}
+
+#[test]
+fn test_run_rrixd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0xDD;
+    cpu.run_op(Opcode::RRIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0xEE);
+    assert_eq!(cpu.regs[Reg::F], 0b10101101);
+}
+
+#[test]
+fn test_run_rriyd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0xDD;
+    cpu.run_op(Opcode::RRIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0xEE);
+    assert_eq!(cpu.regs[Reg::F], 0b10101101);
+}
+
+#[test]
+fn test_run_slar() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0xB1;
+    cpu.run_op(Opcode::SLAR(Reg::H));
+    assert_eq!(cpu.regs[Reg::H], 0x62);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_slahl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0xB1;
+    cpu.run_op(Opcode::SLAHL);
+    assert_eq!(cpu.mem[0x2323], 0x62);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_slaixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0xB1;
+    cpu.run_op(Opcode::SLAIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x62);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_slaiyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0xB1;
+    cpu.run_op(Opcode::SLAIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x62);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_srar() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::L] = 0xB8;
+    cpu.run_op(Opcode::SRAR(Reg::L));
+    assert_eq!(cpu.regs[Reg::L], 0xDC);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
+}
+
+#[test]
+fn test_run_srahl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0xB8;
+    cpu.run_op(Opcode::SRAHL);
+    assert_eq!(cpu.mem[0x2323], 0xDC);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
+}
+
+#[test]
+fn test_run_sraixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0xB8;
+    cpu.run_op(Opcode::SRAIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0xDC);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
+}
+
+#[test]
+fn test_run_sraiyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0xB8;
+    cpu.run_op(Opcode::SRAIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0xDC);
+    assert_eq!(cpu.regs[Reg::F], 0b10001000);
+}
+
+#[test]
+fn test_run_sllr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x31;
+    cpu.run_op(Opcode::SLLR(Reg::A));
+    assert_eq!(cpu.regs[Reg::A], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100100);
+}
+
+#[test]
+fn test_run_sllhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x31;
+    cpu.run_op(Opcode::SLLHL);
+    assert_eq!(cpu.mem[0x2323], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100100);
+}
+
+#[test]
+fn test_run_sllixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0x31;
+    cpu.run_op(Opcode::SLLIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100100);
+}
+
+#[test]
+fn test_run_slliyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x31;
+    cpu.run_op(Opcode::SLLIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x63);
+    assert_eq!(cpu.regs[Reg::F], 0b00100100);
+}
+
+#[test]
+fn test_run_srlr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::B] = 0x8F;
+    cpu.run_op(Opcode::SRLR(Reg::B));
+    assert_eq!(cpu.regs[Reg::B], 0x47);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_srlhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0x8F;
+    cpu.run_op(Opcode::SRLHL);
+    assert_eq!(cpu.mem[0x2323], 0x47);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_srlixd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.ix = 0x1000;
+    cpu.mem[0x0FFE] = 0x8F;
+    cpu.run_op(Opcode::SRLIXD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x47);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_srliyd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x8F;
+    cpu.run_op(Opcode::SRLIYD(-2));
+    assert_eq!(cpu.mem[0x0FFE], 0x47);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_step_cb_prefix() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xCB;
+    cpu.mem[0x0001] = 0x06;
+    cpu.mem[0x0002] = 0xCB;
+    cpu.mem[0x0003] = 0x38;
+    cpu.regs[Reg::B] = 0x03;
+    cpu.regs[Reg::H] = 0x10;
+    cpu.mem[0x1000] = 0x81;
+    assert_eq!(cpu.step(), (Opcode::RLCHL, 15));
+    assert_eq!(cpu.mem[0x1000], 0x03);
+    assert_eq!(cpu.step(), (Opcode::SRLR(Reg::B), 8));
+    assert_eq!(cpu.regs[Reg::B], 0x01);
+    assert!(cpu.get_carry());
+    assert_eq!(cpu.pc, 0x0004);
+}
+
+#[test]
+fn test_run_bitbr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.regs[Reg::B] = 0b10101000;
+    cpu.run_op(Opcode::BITBR(7, Reg::B));
+    assert_eq!(cpu.regs[Reg::F], 0b10111001);
+
+    cpu.run_op(Opcode::BITBR(6, Reg::B));
+    assert_eq!(cpu.regs[Reg::F], 0b01111101);
+    assert_eq!(cpu.regs[Reg::B], 0b10101000);
+}
+
+#[test]
+fn test_run_bitbhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0b00010000;
+    cpu.memptr = 0x2800;
+    cpu.run_op(Opcode::BITBHL(4));
+    assert_eq!(cpu.regs[Reg::F], 0b00111000);
+
+    cpu.run_op(Opcode::BITBHL(3));
+    assert_eq!(cpu.regs[Reg::F], 0b01111100);
+}
+
+#[test]
+fn test_run_bitbhl_memptr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.run_op(Opcode::LDANN(0x07FF));
+    cpu.run_op(Opcode::BITBHL(0));
+    assert_eq!(cpu.regs[Reg::F], 0b01011100);
+}
+
+#[test]
+fn test_run_bitbixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x2010;
+    cpu.mem[0x1FFF] = 0b00000100;
+    cpu.run_op(Opcode::BITBIXD(2, -0x11));
+    assert_eq!(cpu.regs[Reg::F], 0b00011000);
+}
+
+#[test]
+fn test_run_bitbiyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x2010;
+    cpu.mem[0x2015] = 0b11111011;
+    cpu.run_op(Opcode::BITBIYD(2, 0x5));
+    assert_eq!(cpu.regs[Reg::F], 0b01110100);
+}
+
+#[test]
+fn test_run_setbr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b01000010;
+    cpu.regs[Reg::C] = 0b00000001;
+    cpu.run_op(Opcode::SETBR(7, Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0b10000001);
+    assert_eq!(cpu.regs[Reg::F], 0b01000010);
+}
+
+#[test]
+fn test_run_setbhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.run_op(Opcode::SETBHL(3));
+    assert_eq!(cpu.mem[0x2323], 0b00001000);
+}
+
+#[test]
+fn test_run_setbixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.run_op(Opcode::SETBIXD(0, 0x5));
+    assert_eq!(cpu.mem[0x1005], 0b00000001);
+}
+
+#[test]
+fn test_run_setbiyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.run_op(Opcode::SETBIYD(6, 0x5));
+    assert_eq!(cpu.mem[0x1005], 0b01000000);
+}
+
+#[test]
+fn test_run_resbr() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b01000010;
+    cpu.regs[Reg::C] = 0b11111111;
+    cpu.run_op(Opcode::RESBR(7, Reg::C));
+    assert_eq!(cpu.regs[Reg::C], 0b01111111);
+    assert_eq!(cpu.regs[Reg::F], 0b01000010);
+}
+
+#[test]
+fn test_run_resbhl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x23;
+    cpu.regs[Reg::L] = 0x23;
+    cpu.mem[0x2323] = 0b11111111;
+    cpu.run_op(Opcode::RESBHL(3));
+    assert_eq!(cpu.mem[0x2323], 0b11110111);
+}
+
+#[test]
+fn test_run_resbixd() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x1005] = 0b11111111;
+    cpu.run_op(Opcode::RESBIXD(0, 0x5));
+    assert_eq!(cpu.mem[0x1005], 0b11111110);
+}
+
+#[test]
+fn test_run_resbiyd() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x1005] = 0b11111111;
+    cpu.run_op(Opcode::RESBIYD(6, 0x5));
+    assert_eq!(cpu.mem[0x1005], 0b10111111);
+}
+
+#[test]
+fn test_run_rlcixdr() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x1005] = 0x88;
+    cpu.run_op(Opcode::RLCIXDR(0x5, Reg::B));
+    assert_eq!(cpu.mem[0x1005], 0x11);
+    assert_eq!(cpu.regs[Reg::B], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_srliydr() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x0FFE] = 0x8F;
+    cpu.run_op(Opcode::SRLIYDR(-2, Reg::A));
+    assert_eq!(cpu.mem[0x0FFE], 0x47);
+    assert_eq!(cpu.regs[Reg::A], 0x47);
+    assert_eq!(cpu.regs[Reg::F], 0b00000101);
+}
+
+#[test]
+fn test_run_setbixdr() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1000;
+    cpu.mem[0x1005] = 0b00000001;
+    cpu.run_op(Opcode::SETBIXDR(7, 0x5, Reg::C));
+    assert_eq!(cpu.mem[0x1005], 0b10000001);
+    assert_eq!(cpu.regs[Reg::C], 0b10000001);
+}
+
+#[test]
+fn test_run_resbiydr() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1000;
+    cpu.mem[0x1005] = 0b11111111;
+    cpu.run_op(Opcode::RESBIYDR(0, 0x5, Reg::H));
+    assert_eq!(cpu.mem[0x1005], 0b11111110);
+    assert_eq!(cpu.regs[Reg::H], 0b11111110);
+}
+
+#[test]
+fn test_step_indexed_cb_prefix() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xDD;
+    cpu.mem[0x0001] = 0xCB;
+    cpu.mem[0x0002] = 0xFF;
+    cpu.mem[0x0003] = 0x16;
+    cpu.mem[0x0004] = 0xFD;
+    cpu.mem[0x0005] = 0xCB;
+    cpu.mem[0x0006] = 0x01;
+    cpu.mem[0x0007] = 0x4E;
+    cpu.ix = 0x2001;
+    cpu.iy = 0x1FFF;
+    cpu.mem[0x2000] = 0x81;
+    cpu.regs[Reg::F] = 0b00000001;
+    assert_eq!(cpu.step(), (Opcode::RLIXD(-1), 23));
+    assert_eq!(cpu.mem[0x2000], 0x03);
+    assert_eq!(cpu.step(), (Opcode::BITBIYD(1, 1), 20));
+    assert!(!cpu.get_zero());
+    assert_eq!(cpu.pc, 0x0008);
+}
+
+#[test]
+fn test_run_jpnn() {
+    let mut cpu = Z80::new();
+    cpu.run_op(Opcode::JPNN(0x1234));
+    assert_eq!(cpu.pc, 0x1234);
+}
+
+#[test]
+fn test_run_jpccnn() {
+    let conditions = [
+        (Condition::NonZero, 0b00000000, 0b01000000),
+        (Condition::Zero, 0b01000000, 0b00000000),
+        (Condition::NoCarry, 0b00000000, 0b00000001),
+        (Condition::Carry, 0b00000001, 0b00000000),
+        (Condition::ParityOdd, 0b00000000, 0b00000100),
+        (Condition::ParityEven, 0b00000100, 0b00000000),
+        (Condition::PositiveSign, 0b00000000, 0b10000000),
+        (Condition::NegativeSign, 0b10000000, 0b00000000),
+    ];
+    for &(condition, taken, not_taken) in conditions.iter() {
+        let mut cpu = Z80::new();
+        cpu.pc = 0x0003;
+        cpu.regs[Reg::F] = not_taken;
+        cpu.run_op(Opcode::JPCCNN(condition, 0x1234));
+        assert_eq!(cpu.pc, 0x0003);
+        cpu.regs[Reg::F] = taken;
+        cpu.run_op(Opcode::JPCCNN(condition, 0x1234));
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.get_cycles(), 20);
+    }
+}
+
+#[test]
+fn test_run_jre() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1002;
+    cpu.run_op(Opcode::JRE(-0x12));
+    assert_eq!(cpu.pc, 0x0FF0);
+    assert_eq!(cpu.get_cycles(), 12);
+}
+
+#[test]
+fn test_run_jrce() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1002;
+    cpu.run_op(Opcode::JRCE(0x10));
+    assert_eq!(cpu.pc, 0x1002);
+    assert_eq!(cpu.get_cycles(), 7);
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.run_op(Opcode::JRCE(0x10));
+    assert_eq!(cpu.pc, 0x1012);
+    assert_eq!(cpu.get_cycles(), 19);
+}
+
+#[test]
+fn test_run_jrnce() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1002;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.run_op(Opcode::JRNCE(0x10));
+    assert_eq!(cpu.pc, 0x1002);
+    cpu.regs[Reg::F] = 0b00000000;
+    cpu.run_op(Opcode::JRNCE(0x10));
+    assert_eq!(cpu.pc, 0x1012);
+}
+
+#[test]
+fn test_run_jrze() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1002;
+    cpu.run_op(Opcode::JRZE(-0x3));
+    assert_eq!(cpu.pc, 0x1002);
+    cpu.regs[Reg::F] = 0b01000000;
+    cpu.run_op(Opcode::JRZE(-0x3));
+    assert_eq!(cpu.pc, 0x0FFF);
+}
+
+#[test]
+fn test_run_jrnze() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1002;
+    cpu.regs[Reg::F] = 0b01000000;
+    cpu.run_op(Opcode::JRNZE(-0x3));
+    assert_eq!(cpu.pc, 0x1002);
+    cpu.regs[Reg::F] = 0b00000000;
+    cpu.run_op(Opcode::JRNZE(-0x3));
+    assert_eq!(cpu.pc, 0x0FFF);
+}
+
+#[test]
+fn test_run_jre_wraps() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0xFFF0;
+    cpu.run_op(Opcode::JRE(0x20));
+    assert_eq!(cpu.pc, 0x0010);
+}
+
+#[test]
+fn test_run_jphl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::H] = 0x12;
+    cpu.regs[Reg::L] = 0x34;
+    cpu.run_op(Opcode::JPHL);
+    assert_eq!(cpu.pc, 0x1234);
+}
+
+#[test]
+fn test_run_jpix() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x1234;
+    cpu.run_op(Opcode::JPIX);
+    assert_eq!(cpu.pc, 0x1234);
+}
+
+#[test]
+fn test_run_jpiy() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x1234;
+    cpu.run_op(Opcode::JPIY);
+    assert_eq!(cpu.pc, 0x1234);
+}
+
+#[test]
+fn test_run_djnze() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0x06;
+    cpu.mem[0x0001] = 0x03;
+    cpu.mem[0x0002] = 0x3C;
+    cpu.mem[0x0003] = 0x10;
+    cpu.mem[0x0004] = 0xFD;
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0005), 7 + 3 * 4 + 13 + 13 + 8);
+    assert_eq!(cpu.regs[Reg::A], 3);
+    assert_eq!(cpu.regs[Reg::B], 0);
+}
+
+#[test]
+fn test_run_callnn() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1A47;
+    cpu.sp = 0x3002;
+    cpu.run_op(Opcode::CALLNN(0x2135));
+    assert_eq!(cpu.pc, 0x2135);
+    assert_eq!(cpu.sp, 0x3000);
+    assert_eq!(cpu.mem[0x3001], 0x1A);
+    assert_eq!(cpu.mem[0x3000], 0x47);
+}
+
+#[test]
+fn test_run_callccnn() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x1A47;
+    cpu.sp = 0x3002;
+    cpu.run_op(Opcode::CALLCCNN(Condition::Carry, 0x2135));
+    assert_eq!(cpu.pc, 0x1A47);
+    assert_eq!(cpu.sp, 0x3002);
+    assert_eq!(cpu.get_cycles(), 10);
+
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.run_op(Opcode::CALLCCNN(Condition::Carry, 0x2135));
+    assert_eq!(cpu.pc, 0x2135);
+    assert_eq!(cpu.sp, 0x3000);
+    assert_eq!(cpu.mem[0x3001], 0x1A);
+    assert_eq!(cpu.mem[0x3000], 0x47);
+    assert_eq!(cpu.get_cycles(), 27);
+}
+
+#[test]
+fn test_run_ret() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x3535;
+    cpu.sp = 0x2000;
+    cpu.mem[0x2000] = 0xB5;
+    cpu.mem[0x2001] = 0x18;
+    cpu.run_op(Opcode::RET);
+    assert_eq!(cpu.pc, 0x18B5);
+    assert_eq!(cpu.sp, 0x2002);
+}
+
+#[test]
+fn test_run_retcc() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x3535;
+    cpu.sp = 0x2000;
+    cpu.mem[0x2000] = 0xB5;
+    cpu.mem[0x2001] = 0x18;
+    cpu.run_op(Opcode::RETCC(Condition::Zero));
+    assert_eq!(cpu.pc, 0x3535);
+    assert_eq!(cpu.sp, 0x2000);
+    assert_eq!(cpu.get_cycles(), 5);
+
+    cpu.regs[Reg::F] = 0b01000000;
+    cpu.run_op(Opcode::RETCC(Condition::Zero));
+    assert_eq!(cpu.pc, 0x18B5);
+    assert_eq!(cpu.sp, 0x2002);
+    assert_eq!(cpu.get_cycles(), 16);
+}
+
+#[test]
+fn test_run_reti() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x2000;
+    cpu.mem[0x2000] = 0xB5;
+    cpu.mem[0x2001] = 0x18;
+    cpu.iff2 = true;
+    cpu.run_op(Opcode::RETI);
+    assert_eq!(cpu.pc, 0x18B5);
+    assert_eq!(cpu.sp, 0x2002);
+    assert!(cpu.iff1);
+}
+
+#[test]
+fn test_run_retn() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x2000;
+    cpu.mem[0x2000] = 0xB5;
+    cpu.mem[0x2001] = 0x18;
+    cpu.iff1 = false;
+    cpu.iff2 = true;
+    cpu.run_op(Opcode::RETN);
+    assert_eq!(cpu.pc, 0x18B5);
+    assert_eq!(cpu.sp, 0x2002);
+    assert!(cpu.iff1);
+    assert!(cpu.iff2);
+}
+
+#[test]
+fn test_run_retp() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x15B4;
+    cpu.sp = 0x3002;
+    cpu.run_op(Opcode::RETP(0x18));
+    assert_eq!(cpu.pc, 0x0018);
+    assert_eq!(cpu.sp, 0x3000);
+    assert_eq!(cpu.mem[0x3001], 0x15);
+    assert_eq!(cpu.mem[0x3000], 0xB4);
+}
+
+#[test]
+fn test_run_call_and_return() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x8000;
+    // CALL 0x0010; HALT; ...; 0x0010: INC A; RET
+    cpu.mem[0x0000] = 0xCD;
+    cpu.mem[0x0001] = 0x10;
+    cpu.mem[0x0002] = 0x00;
+    cpu.mem[0x0003] = 0x76;
+    cpu.mem[0x0010] = 0x3C;
+    cpu.mem[0x0011] = 0xC9;
+    assert_eq!(cpu.run_until(|cpu| cpu.get_pc() == 0x0003), 17 + 4 + 10);
+    assert_eq!(cpu.regs[Reg::A], 1);
+    assert_eq!(cpu.sp, 0x8000);
+}
+
+#[test]
+fn test_run_addhlss() {
+    let mut cpu = Z80::new();
+    cpu.set_big_reg(BigReg::HL, 0x4242);
+    cpu.set_big_reg(BigReg::DE, 0x1111);
+    cpu.run_op(Opcode::ADDHLSS(BigReg::DE));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x5353);
+    assert_eq!(cpu.regs[Reg::F], 0b00000000);
+
+    cpu.set_big_reg(BigReg::HL, 0x0FFF);
+    cpu.set_big_reg(BigReg::BC, 0x0001);
+    cpu.run_op(Opcode::ADDHLSS(BigReg::BC));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x1000);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
+}
+
+#[test]
+fn test_run_addhlss_keeps_sign_zero_parity() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b11000110;
+    cpu.set_big_reg(BigReg::HL, 0xFFFF);
+    cpu.sp = 0x0002;
+    cpu.run_op(Opcode::ADDHLSS(BigReg::SP));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x0001);
+    assert_eq!(cpu.regs[Reg::F], 0b11010101);
+}
+
+#[test]
+fn test_run_adchlss() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.set_big_reg(BigReg::HL, 0x5437);
+    cpu.set_big_reg(BigReg::BC, 0x2222);
+    cpu.run_op(Opcode::ADCHLSS(BigReg::BC));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x765A);
+    assert_eq!(cpu.regs[Reg::F], 0b00100000);
+
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.set_big_reg(BigReg::HL, 0x7FFF);
+    cpu.set_big_reg(BigReg::BC, 0x0000);
+    cpu.run_op(Opcode::ADCHLSS(BigReg::BC));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x8000);
+    assert_eq!(cpu.regs[Reg::F], 0b10010100);
+
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.set_big_reg(BigReg::HL, 0xFFFF);
+    cpu.run_op(Opcode::ADCHLSS(BigReg::BC));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x0000);
+    assert_eq!(cpu.regs[Reg::F], 0b01010001);
+}
+
+#[test]
+fn test_run_sbchlss() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.set_big_reg(BigReg::HL, 0x9999);
+    cpu.set_big_reg(BigReg::DE, 0x1111);
+    cpu.run_op(Opcode::SBCHLSS(BigReg::DE));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x8887);
+    assert_eq!(cpu.regs[Reg::F], 0b10001010);
+
+    cpu.regs[Reg::F] = 0b00000000;
+    cpu.set_big_reg(BigReg::HL, 0x8000);
+    cpu.set_big_reg(BigReg::DE, 0x0001);
+    cpu.run_op(Opcode::SBCHLSS(BigReg::DE));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x7FFF);
+    assert_eq!(cpu.regs[Reg::F], 0b00111110);
+
+    cpu.regs[Reg::F] = 0b00000000;
+    cpu.run_op(Opcode::SBCHLSS(BigReg::HL));
+    assert_eq!(cpu.get_big_reg(BigReg::HL), 0x0000);
+    assert_eq!(cpu.regs[Reg::F], 0b01000010);
+}
+
+#[test]
+fn test_run_addixpp() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x0FFF;
+    cpu.set_big_reg(BigReg::BC, 0x0001);
+    cpu.run_op(Opcode::ADDIXPP(BigReg::BC));
+    assert_eq!(cpu.ix, 0x1000);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
+
+    cpu.run_op(Opcode::ADDIXPP(BigReg::IX));
+    assert_eq!(cpu.ix, 0x2000);
+    assert_eq!(cpu.regs[Reg::F], 0b00100000);
+}
+
+#[test]
+fn test_run_addiyrr() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x4242;
+    cpu.set_big_reg(BigReg::DE, 0x1111);
+    cpu.run_op(Opcode::ADDIYRR(BigReg::DE));
+    assert_eq!(cpu.iy, 0x5353);
+    assert_eq!(cpu.regs[Reg::F], 0b00000000);
+}
+
+#[test]
+fn test_run_incss() {
+    let mut cpu = Z80::new();
+    cpu.set_big_reg(BigReg::DE, 0xFFFF);
+    cpu.regs[Reg::F] = 0b00010000;
+    cpu.run_op(Opcode::INCSS(BigReg::DE));
+    assert_eq!(cpu.get_big_reg(BigReg::DE), 0x0000);
+    assert_eq!(cpu.regs[Reg::F], 0b00010000);
+}
+
+#[test]
+fn test_run_incix() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x3300;
+    cpu.run_op(Opcode::INCIX);
+    assert_eq!(cpu.ix, 0x3301);
+}
+
+#[test]
+fn test_run_inciy() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x2977;
+    cpu.run_op(Opcode::INCIY);
+    assert_eq!(cpu.iy, 0x2978);
+}
+
+#[test]
+fn test_run_decss() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x0000;
+    cpu.run_op(Opcode::DECSS(BigReg::SP));
+    assert_eq!(cpu.sp, 0xFFFF);
+    assert_eq!(cpu.regs[Reg::F], 0b00000000);
+}
+
+#[test]
+fn test_run_decix() {
+    let mut cpu = Z80::new();
+    cpu.ix = 0x2006;
+    cpu.run_op(Opcode::DECIX);
+    assert_eq!(cpu.ix, 0x2005);
+}
+
+#[test]
+fn test_run_deciy() {
+    let mut cpu = Z80::new();
+    cpu.iy = 0x7649;
+    cpu.run_op(Opcode::DECIY);
+    assert_eq!(cpu.iy, 0x7648);
+}
+
+#[test]
+fn test_run_push_pop_af() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0xF5;
+    cpu.mem[0x0001] = 0xC1;
+    cpu.sp = 0x1000;
+    cpu.regs[Reg::A] = 0x22;
+    cpu.regs[Reg::F] = 0x33;
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.get_big_reg(BigReg::BC), 0x2233);
+    assert_eq!(cpu.sp, 0x1000);
+}
+
+#[test]
+fn test_run_daa() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x15;
+    cpu.run_op(Opcode::ADDAN(0x27));
+    cpu.run_op(Opcode::DAA);
+    assert_eq!(cpu.regs[Reg::A], 0x42);
+    assert_eq!(cpu.regs[Reg::F], 0b00010100);
+
+    cpu.run_op(Opcode::SUBAN(0x15));
+    cpu.run_op(Opcode::DAA);
+    assert_eq!(cpu.regs[Reg::A], 0x27);
+    assert_eq!(cpu.regs[Reg::F], 0b00100110);
+
+    cpu.regs[Reg::A] = 0x99;
+    cpu.run_op(Opcode::ADDAN(0x01));
+    cpu.run_op(Opcode::DAA);
+    assert_eq!(cpu.regs[Reg::A], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01010101);
+}
+
+#[test]
+fn test_run_daa_bcd_arithmetic() {
+    fn bcd(value: u8) -> u8 { (value / 10) << 4 | (value % 10) }
+
+    let mut cpu = Z80::new();
+    for a in 0..100u8 {
+        for b in 0..100u8 {
+            cpu.regs[Reg::A] = bcd(a);
+            cpu.run_op(Opcode::ADDAN(bcd(b)));
+            cpu.run_op(Opcode::DAA);
+            assert_eq!(cpu.regs[Reg::A], bcd((a + b) % 100));
+            assert_eq!(cpu.get_carry(), a + b >= 100);
+
+            cpu.regs[Reg::A] = bcd(a);
+            cpu.run_op(Opcode::SUBAN(bcd(b)));
+            cpu.run_op(Opcode::DAA);
+            assert_eq!(cpu.regs[Reg::A], bcd((100 + a - b) % 100));
+            assert_eq!(cpu.get_carry(), a < b);
+        }
+    }
+}
+
+#[test]
+fn test_run_cpl() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0b10110100;
+    cpu.run_op(Opcode::CPL);
+    assert_eq!(cpu.regs[Reg::A], 0b01001011);
+    assert_eq!(cpu.regs[Reg::F], 0b00011010);
+}
+
+#[test]
+fn test_run_neg() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x98;
+    cpu.run_op(Opcode::NEG);
+    assert_eq!(cpu.regs[Reg::A], 0x68);
+    assert_eq!(cpu.regs[Reg::F], 0b00111011);
+
+    cpu.regs[Reg::A] = 0x80;
+    cpu.run_op(Opcode::NEG);
+    assert_eq!(cpu.regs[Reg::A], 0x80);
+    assert_eq!(cpu.regs[Reg::F], 0b10000111);
+
+    cpu.regs[Reg::A] = 0x00;
+    cpu.run_op(Opcode::NEG);
+    assert_eq!(cpu.regs[Reg::A], 0x00);
+    assert_eq!(cpu.regs[Reg::F], 0b01000010);
+}
+
+#[test]
+fn test_run_ccf() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x28;
+    cpu.regs[Reg::F] = 0b11000111;
+    cpu.run_op(Opcode::CCF);
+    assert_eq!(cpu.regs[Reg::F], 0b11111100);
+
+    cpu.run_op(Opcode::CCF);
+    assert_eq!(cpu.regs[Reg::F], 0b11101101);
+}
+
+#[test]
+fn test_run_scf() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x20;
+    cpu.regs[Reg::F] = 0b11010110;
+    cpu.run_op(Opcode::SCF);
+    assert_eq!(cpu.regs[Reg::F], 0b11100101);
+}
+
+#[test]
+fn test_run_rlca() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x88;
+    cpu.regs[Reg::F] = 0b11010110;
+    cpu.run_op(Opcode::RLCA);
+    assert_eq!(cpu.regs[Reg::A], 0x11);
+    assert_eq!(cpu.regs[Reg::F], 0b11000101);
+}
+
+#[test]
+fn test_run_rrca() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x11;
+    cpu.run_op(Opcode::RRCA);
+    assert_eq!(cpu.regs[Reg::A], 0x88);
+    assert_eq!(cpu.regs[Reg::F], 0b00001001);
+}
+
+#[test]
+fn test_run_rla() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x76;
+    cpu.regs[Reg::F] = 0b00000001;
+    cpu.run_op(Opcode::RLA);
+    assert_eq!(cpu.regs[Reg::A], 0xED);
+    assert_eq!(cpu.regs[Reg::F], 0b00101000);
+}
+
+#[test]
+fn test_run_rra() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0xE1;
+    cpu.run_op(Opcode::RRA);
+    assert_eq!(cpu.regs[Reg::A], 0x70);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_rld() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x7A;
+    cpu.regs[Reg::F] = 0b00010011;
+    cpu.regs[Reg::H] = 0x50;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.mem[0x5000] = 0x31;
+    cpu.run_op(Opcode::RLD);
+    assert_eq!(cpu.regs[Reg::A], 0x73);
+    assert_eq!(cpu.mem[0x5000], 0x1A);
+    assert_eq!(cpu.regs[Reg::F], 0b00100001);
+}
+
+#[test]
+fn test_run_rrd() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x84;
+    cpu.regs[Reg::H] = 0x50;
+    cpu.regs[Reg::L] = 0x00;
+    cpu.mem[0x5000] = 0x20;
+    cpu.run_op(Opcode::RRD);
+    assert_eq!(cpu.regs[Reg::A], 0x80);
+    assert_eq!(cpu.mem[0x5000], 0x42);
+    assert_eq!(cpu.regs[Reg::F], 0b10000000);
+}
+
+#[test]
+fn test_run_di() {
+    let mut cpu = Z80::new();
+    cpu.iff1 = true;
+    cpu.iff2 = true;
+    cpu.run_op(Opcode::DI);
+    assert!(!cpu.iff1);
+    assert!(!cpu.iff2);
+}
+
+#[test]
+fn test_run_ei() {
+    let mut cpu = Z80::new();
+    cpu.run_op(Opcode::EI);
+    assert!(cpu.iff1);
+    assert!(cpu.iff2);
+}
+
+#[test]
+fn test_run_im() {
+    let mut cpu = Z80::new();
+    cpu.run_op(Opcode::IM2);
+    assert_eq!(cpu.get_im(), 2);
+    cpu.run_op(Opcode::IM1);
+    assert_eq!(cpu.get_im(), 1);
+    cpu.run_op(Opcode::IM0);
+    assert_eq!(cpu.get_im(), 0);
+}
+
+#[test]
+fn test_interrupt_im1_after_ei_delay() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.im = 1;
+    cpu.mem[0x0000] = 0xFB;
+    cpu.assert_int(0xFF);
+    assert_eq!(cpu.step(), (Opcode::EI, 4));
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.step(), (Opcode::RETP(0x38), 13));
+    assert_eq!(cpu.pc, 0x0038);
+    assert_eq!(cpu.sp, 0x0FFE);
+    assert_eq!(cpu.mem[0x0FFE], 0x02);
+    assert_eq!(cpu.mem[0x0FFF], 0x00);
+    assert!(!cpu.iff1);
+    assert!(!cpu.iff2);
+
+    // INT stays asserted but interrupts are now disabled
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+}
+
+#[test]
+fn test_interrupt_ignored_when_disabled() {
+    let mut cpu = Z80::new();
+    cpu.im = 1;
+    cpu.assert_int(0xFF);
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.pc, 0x0001);
+
+    cpu.iff1 = true;
+    cpu.deassert_int();
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.pc, 0x0002);
+}
+
+#[test]
+fn test_interrupt_im0() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.pc = 0x1234;
+    cpu.iff1 = true;
+    cpu.assert_int(0xCF);
+    assert_eq!(cpu.step(), (Opcode::RETP(0x08), 13));
+    assert_eq!(cpu.pc, 0x0008);
+    assert_eq!(cpu.mem[0x0FFE], 0x34);
+    assert_eq!(cpu.mem[0x0FFF], 0x12);
+}
+
+#[test]
+fn test_interrupt_im2() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.pc = 0x1234;
+    cpu.i = 0x20;
+    cpu.im = 2;
+    cpu.iff1 = true;
+    cpu.mem[0x20FE] = 0x78;
+    cpu.mem[0x20FF] = 0x56;
+    cpu.assert_int(0xFE);
+    assert_eq!(cpu.step(), (Opcode::CALLNN(0x5678), 19));
+    assert_eq!(cpu.pc, 0x5678);
+    assert_eq!(cpu.mem[0x0FFE], 0x34);
+    assert_eq!(cpu.mem[0x0FFF], 0x12);
+}
+
+#[test]
+fn test_nmi() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.pc = 0x1234;
+    cpu.iff1 = true;
+    cpu.iff2 = true;
+    cpu.mem[0x0066] = 0xED;
+    cpu.mem[0x0067] = 0x45;
+    cpu.pulse_nmi();
+    assert_eq!(cpu.step(), (Opcode::CALLNN(0x0066), 11));
+    assert_eq!(cpu.pc, 0x0066);
+    assert!(!cpu.iff1);
+    assert!(cpu.iff2);
+
+    assert_eq!(cpu.step(), (Opcode::RETN, 14));
+    assert_eq!(cpu.pc, 0x1234);
+    assert!(cpu.iff1);
+}
+
+#[test]
+fn test_interrupt_im0_call() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.pc = 0x0200;
+    cpu.iff1 = true;
+    cpu.assert_int_instruction(&[0xCD, 0x34, 0x12]);
+    assert_eq!(cpu.step(), (Opcode::CALLNN(0x1234), 19));
+    assert_eq!(cpu.pc, 0x1234);
+    assert_eq!(cpu.sp, 0x0FFE);
+    assert_eq!(cpu.mem[0x0FFE], 0x00);
+    assert_eq!(cpu.mem[0x0FFF], 0x02);
+}
+
+#[test]
+fn test_interrupt_im0_keeps_pc() {
+    let mut cpu = Z80::new();
+    cpu.pc = 0x0200;
+    cpu.iff1 = true;
+    cpu.assert_int_instruction(&[0x3E, 0x42]);
+    assert_eq!(cpu.step(), (Opcode::LDRN(Reg::A, 0x42), 9));
+    assert_eq!(cpu.regs[Reg::A], 0x42);
+    assert_eq!(cpu.pc, 0x0200);
+}
+
+#[test]
+fn test_run_halt() {
+    let mut cpu = Z80::new();
+    cpu.mem[0x0000] = 0x76;
+    assert_eq!(cpu.step(), (Opcode::HALT, 4));
+    assert!(cpu.get_halted());
+    assert_eq!(cpu.pc, 0x0001);
+
+    cpu.r = 0xFE;
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert_eq!(cpu.step(), (Opcode::NOP, 4));
+    assert!(cpu.get_halted());
+    assert_eq!(cpu.pc, 0x0001);
+    assert_eq!(cpu.r, 0x80);
+    assert_eq!(cpu.get_cycles(), 12);
+}
+
+#[test]
+fn test_interrupt_resumes_after_halt() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.im = 1;
+    // EI; HALT; INC A ... 0x0038: RETI
+    cpu.mem[0x0000] = 0xFB;
+    cpu.mem[0x0001] = 0x76;
+    cpu.mem[0x0002] = 0x3C;
+    cpu.mem[0x0038] = 0xED;
+    cpu.mem[0x0039] = 0x4D;
+    cpu.step();
+    cpu.step();
+    cpu.step();
+    assert!(cpu.get_halted());
+
+    cpu.assert_int(0xFF);
+    assert_eq!(cpu.step(), (Opcode::RETP(0x38), 13));
+    assert!(!cpu.get_halted());
+    cpu.deassert_int();
+    assert_eq!(cpu.step(), (Opcode::RETI, 14));
+    assert_eq!(cpu.pc, 0x0002);
+    assert_eq!(cpu.step(), (Opcode::INCR(Reg::A), 4));
+    assert_eq!(cpu.regs[Reg::A], 1);
+}
+
+#[test]
+fn test_nmi_resumes_after_halt() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.mem[0x0000] = 0x76;
+    cpu.step();
+    cpu.pulse_nmi();
+    assert_eq!(cpu.step(), (Opcode::CALLNN(0x0066), 11));
+    assert!(!cpu.get_halted());
+    assert_eq!(cpu.mem[0x0FFE], 0x01);
+}
+
+#[test]
+fn test_step_increments_r() {
+    let mut cpu = Z80::new();
+    // NOP; RLC B; NEG; RLC (IX+1); unused DD prefix before NOP
+    let program = [0x00, 0xCB, 0x00, 0xED, 0x44, 0xDD, 0xCB, 0x01, 0x06, 0xDD, 0x00];
+    for (address, &byte) in program.iter().enumerate() {
+        cpu.mem[address] = byte;
+    }
+    cpu.r = 0x7E;
+    cpu.step();
+    assert_eq!(cpu.r, 0x7F);
+    cpu.step();
+    assert_eq!(cpu.r, 0x01);
+    cpu.step();
+    assert_eq!(cpu.r, 0x03);
+    cpu.step();
+    assert_eq!(cpu.r, 0x05);
+    cpu.step();
+    assert_eq!(cpu.r, 0x07);
+    cpu.step();
+    assert_eq!(cpu.r, 0x07);
+    assert_eq!(cpu.pc, 0x000B);
+}
+
+#[test]
+fn test_step_preserves_r_bit_7() {
+    let mut cpu = Z80::new();
+    cpu.r = 0xFF;
+    cpu.step();
+    assert_eq!(cpu.r, 0x80);
+}
+
+#[test]
+fn test_interrupt_increments_r() {
+    let mut cpu = Z80::new();
+    cpu.im = 1;
+    cpu.iff1 = true;
+    cpu.assert_int(0xFF);
+    cpu.step();
+    assert_eq!(cpu.r, 0x01);
+}
+
+#[test]
+fn test_run_ldai_flags() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::F] = 0b10000001;
+    cpu.iff2 = true;
+    cpu.i = 0x00;
+    cpu.run_op(Opcode::LDAI);
+    assert_eq!(cpu.regs[Reg::F], 0b01000101);
+
+    cpu.iff2 = false;
+    cpu.i = 0x28;
+    cpu.run_op(Opcode::LDAI);
+    assert_eq!(cpu.regs[Reg::F], 0b00101001);
+}
+
+#[test]
+fn test_run_ldar_flags() {
+    let mut cpu = Z80::new();
+    cpu.iff2 = true;
+    cpu.r = 0x80;
+    cpu.run_op(Opcode::LDAR);
+    assert_eq!(cpu.regs[Reg::A], 0x80);
+    assert_eq!(cpu.regs[Reg::F], 0b10000100);
+}
+
+#[test]
+fn test_interrupt_after_ldai_clears_parity() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.im = 1;
+    cpu.iff1 = true;
+    cpu.iff2 = true;
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0x57;
+    assert_eq!(cpu.step(), (Opcode::LDAI, 9));
+    assert!(cpu.get_parity_overflow());
+
+    cpu.assert_int(0xFF);
+    assert_eq!(cpu.step(), (Opcode::RETP(0x38), 13));
+    assert!(!cpu.get_parity_overflow());
+}
+
+#[test]
+fn test_interrupt_later_keeps_parity() {
+    let mut cpu = Z80::new();
+    cpu.sp = 0x1000;
+    cpu.im = 1;
+    cpu.iff1 = true;
+    cpu.iff2 = true;
+    cpu.mem[0x0000] = 0xED;
+    cpu.mem[0x0001] = 0x57;
+    cpu.step();
+    cpu.step();
+    cpu.assert_int(0xFF);
+    cpu.step();
+    assert!(cpu.get_parity_overflow());
+}
+
+#[test]
+fn test_reset() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::A] = 0x12;
+    cpu.sp = 0x3456;
+    cpu.ix = 0x789A;
+    cpu.pc = 0x1234;
+    cpu.i = 0x55;
+    cpu.r = 0x66;
+    cpu.iff1 = true;
+    cpu.iff2 = true;
+    cpu.im = 2;
+    cpu.halted = true;
+    cpu.reset();
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.i, 0x00);
+    assert_eq!(cpu.r, 0x00);
+    assert!(!cpu.iff1);
+    assert!(!cpu.iff2);
+    assert_eq!(cpu.get_im(), 0);
+    assert!(!cpu.get_halted());
+    assert_eq!(cpu.regs[Reg::A], 0x12);
+    assert_eq!(cpu.sp, 0x3456);
+    assert_eq!(cpu.ix, 0x789A);
+}
+
+#[test]
+fn test_power_on_zeroed() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::B] = 0x12;
+    cpu.sp = 0x3456;
+    cpu.pc = 0x1234;
+    cpu.power_on(PowerOn::Zeroed);
+    assert_eq!(cpu.get_big_reg(BigReg::BC), 0x0000);
+    assert_eq!(cpu.sp, 0x0000);
+    assert_eq!(cpu.pc, 0x0000);
+}
+
+#[test]
+fn test_power_on_typical() {
+    let mut cpu = Z80::new();
+    cpu.regs[Reg::B] = 0x12;
+    cpu.pc = 0x1234;
+    cpu.power_on(PowerOn::Typical);
+    assert_eq!(cpu.get_big_reg(BigReg::AF), 0xFFFF);
+    assert_eq!(cpu.sp, 0xFFFF);
+    assert_eq!(cpu.get_big_reg(BigReg::BC), 0x0000);
+    assert_eq!(cpu.pc, 0x0000);
+}
+
+#[test]
+fn test_power_on_randomized() {
+    let mut cpu1 = Z80::new();
+    let mut cpu2 = Z80::new();
+    let mut cpu3 = Z80::new();
+    cpu1.power_on(PowerOn::Randomized(42));
+    cpu2.power_on(PowerOn::Randomized(42));
+    cpu3.power_on(PowerOn::Randomized(43));
+    assert_eq!(cpu1.regs, cpu2.regs);
+    assert_eq!(cpu1.sp, cpu2.sp);
+    assert!(cpu1.mem[..] == cpu2.mem[..]);
+    assert!(cpu1.regs != cpu3.regs || cpu1.sp != cpu3.sp);
+    assert!(cpu1.mem.iter().any(|&byte| byte != 0));
+    assert_eq!(cpu1.pc, 0x0000);
+    assert_eq!(cpu1.i, 0x00);
+    assert_eq!(cpu1.r, 0x00);
 }
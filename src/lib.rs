@@ -1,2 +1,5 @@
 pub mod ops;
 pub mod cpu;
+
+pub use cpu::Z80;
+pub use cpu::PowerOn;
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Condition {
     NonZero = 0b000,
     Zero = 0b001,
@@ -10,7 +10,7 @@ pub enum Condition {
     NegativeSign = 0b111,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Reg {
     A = 0,
     B = 1,
@@ -30,7 +30,7 @@ pub enum Reg {
     L2 = 15,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BigReg {
     BC = 0,
     DE = 1,
@@ -45,9 +45,10 @@ pub type Bit = u8;
 pub type IODevice = u8;
 pub type Value = u8;
 pub type Address = u16;
-pub type Displacement = u8;
+/// Signed offset added to IX or IY by the indexed addressing mode.
+pub type Displacement = i8;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     LDRR(Reg, Reg),
     LDRN(Reg, Value),
@@ -114,7 +115,7 @@ pub enum Opcode {
     ADDAIYD(Displacement),
     ADCAR(Reg),
     ADCAN(Value),
-    ADCAHL(Value),
+    ADCAHL,
     ADCAIXD(Displacement),
     ADCAIYD(Displacement),
     SUBAR(Reg),
@@ -186,26 +187,50 @@ pub enum Opcode {
     RLCHL,
     RLCIXD(Displacement),
     RLCIYD(Displacement),
+    RLCIXDR(Displacement, Reg),
+    RLCIYDR(Displacement, Reg),
     RRCR(Reg),
     RRCHL,
     RRCIXD(Displacement),
     RRCIYD(Displacement),
+    RRCIXDR(Displacement, Reg),
+    RRCIYDR(Displacement, Reg),
+    RLR(Reg),
+    RLHL,
+    RLIXD(Displacement),
+    RLIYD(Displacement),
+    RLIXDR(Displacement, Reg),
+    RLIYDR(Displacement, Reg),
     RRR(Reg),
     RRHL,
     RRIXD(Displacement),
     RRIYD(Displacement),
+    RRIXDR(Displacement, Reg),
+    RRIYDR(Displacement, Reg),
     SLAR(Reg),
     SLAHL,
     SLAIXD(Displacement),
     SLAIYD(Displacement),
+    SLAIXDR(Displacement, Reg),
+    SLAIYDR(Displacement, Reg),
     SRAR(Reg),
     SRAHL,
     SRAIXD(Displacement),
     SRAIYD(Displacement),
+    SRAIXDR(Displacement, Reg),
+    SRAIYDR(Displacement, Reg),
+    SLLR(Reg),
+    SLLHL,
+    SLLIXD(Displacement),
+    SLLIYD(Displacement),
+    SLLIXDR(Displacement, Reg),
+    SLLIYDR(Displacement, Reg),
     SRLR(Reg),
     SRLHL,
     SRLIXD(Displacement),
     SRLIYD(Displacement),
+    SRLIXDR(Displacement, Reg),
+    SRLIYDR(Displacement, Reg),
     RLD,
     RRD,
     BITBR(Bit, Reg),
@@ -216,21 +241,25 @@ pub enum Opcode {
     SETBHL(Bit),
     SETBIXD(Bit, Displacement),
     SETBIYD(Bit, Displacement),
+    SETBIXDR(Bit, Displacement, Reg),
+    SETBIYDR(Bit, Displacement, Reg),
     RESBR(Bit, Reg),
     RESBHL(Bit),
     RESBIXD(Bit, Displacement),
     RESBIYD(Bit, Displacement),
+    RESBIXDR(Bit, Displacement, Reg),
+    RESBIYDR(Bit, Displacement, Reg),
     JPNN(Address),
     JPCCNN(Condition, Address),
-    JRE(Value),
-    JRCE(Value),
-    JRNCE(Value),
-    JRZE(Value),
-    JRNZE(Value),
+    JRE(Displacement),
+    JRCE(Displacement),
+    JRNCE(Displacement),
+    JRZE(Displacement),
+    JRNZE(Displacement),
     JPHL,
     JPIX,
     JPIY,
-    DJNZE(Value),
+    DJNZE(Displacement),
     CALLNN(Address),
     CALLCCNN(Condition, Address),
     RET,
@@ -240,14 +269,90 @@ pub enum Opcode {
     RETP(Value),
     INAN(IODevice),
     INRC(Reg),
+    INFC,
     INI,
     INIR,
     IND,
     INDR,
     OUTNA(IODevice),
     OUTCR(Reg),
+    OUTC0,
     OUTI,
     OTIR,
     OUTD,
     OTDR,
 }
+
+impl Opcode {
+    /// Returns the number of T-states the instruction takes. Conditional and
+    /// repeating instructions report the time taken when the condition is false
+    /// or the repetition ends; the CPU adds the extra time when it applies.
+    pub fn cycles(&self) -> u32 {
+        match *self {
+            Opcode::LDRR(..) | Opcode::EXDEHL | Opcode::EXAFAF2 | Opcode::EXX |
+            Opcode::ADDAR(..) | Opcode::ADCAR(..) | Opcode::SUBAR(..) | Opcode::SBCAR(..) |
+            Opcode::ANDAR(..) | Opcode::ORAR(..) | Opcode::XORAR(..) | Opcode::CPAR(..) |
+            Opcode::INCR(..) | Opcode::DECR(..) | Opcode::DAA | Opcode::CPL | Opcode::CCF |
+            Opcode::SCF | Opcode::NOP | Opcode::HALT | Opcode::DI | Opcode::EI | Opcode::RLCA |
+            Opcode::RLA | Opcode::RRCA | Opcode::RRA | Opcode::JPHL => 4,
+            Opcode::RETCC(..) => 5,
+            Opcode::LDSPHL | Opcode::INCSS(..) | Opcode::DECSS(..) => 6,
+            Opcode::LDRN(..) | Opcode::LDRHL(..) | Opcode::LDHLR(..) | Opcode::LDABC |
+            Opcode::LDADE | Opcode::LDBCA | Opcode::LDDEA | Opcode::ADDAN(..) | Opcode::ADDAHL |
+            Opcode::ADCAN(..) | Opcode::ADCAHL | Opcode::SUBAN(..) | Opcode::SUBAHL |
+            Opcode::SBCAN(..) | Opcode::SBCAHL | Opcode::ANDAN(..) | Opcode::ANDAHL |
+            Opcode::ORAN(..) | Opcode::ORAHL | Opcode::XORAN(..) | Opcode::XORAHL |
+            Opcode::CPAN(..) | Opcode::CPAHL | Opcode::JRCE(..) | Opcode::JRNCE(..) |
+            Opcode::JRZE(..) | Opcode::JRNZE(..) => 7,
+            Opcode::NEG | Opcode::IM0 | Opcode::IM1 | Opcode::IM2 | Opcode::RLCR(..) |
+            Opcode::RRCR(..) | Opcode::RLR(..) | Opcode::RRR(..) | Opcode::SLAR(..) |
+            Opcode::SRAR(..) | Opcode::SLLR(..) | Opcode::SRLR(..) | Opcode::BITBR(..) | Opcode::SETBR(..) | Opcode::RESBR(..) |
+            Opcode::JPIX | Opcode::JPIY | Opcode::DJNZE(..) => 8,
+            Opcode::LDAI | Opcode::LDAR | Opcode::LDIA | Opcode::LDRA => 9,
+            Opcode::LDHLN(..) | Opcode::LDDDNN(..) | Opcode::LDSPIX | Opcode::LDSPIY |
+            Opcode::POPQQ(..) | Opcode::INCIX | Opcode::INCIY | Opcode::DECIX | Opcode::DECIY |
+            Opcode::JPNN(..) | Opcode::JPCCNN(..) | Opcode::CALLCCNN(..) | Opcode::RET => 10,
+            Opcode::PUSHQQ(..) | Opcode::INCHL | Opcode::DECHL | Opcode::ADDHLSS(..) |
+            Opcode::RETP(..) | Opcode::INAN(..) | Opcode::OUTNA(..) => 11,
+            Opcode::BITBHL(..) | Opcode::JRE(..) | Opcode::INRC(..) | Opcode::OUTCR(..) |
+            Opcode::INFC | Opcode::OUTC0 => 12,
+            Opcode::LDANN(..) | Opcode::LDNNA(..) => 13,
+            Opcode::LDIXNN(..) | Opcode::LDIYNN(..) | Opcode::POPIX | Opcode::POPIY |
+            Opcode::RETI | Opcode::RETN => 14,
+            Opcode::PUSHIX | Opcode::PUSHIY | Opcode::ADCHLSS(..) | Opcode::SBCHLSS(..) |
+            Opcode::ADDIXPP(..) | Opcode::ADDIYRR(..) | Opcode::RLCHL | Opcode::RRCHL |
+            Opcode::RLHL | Opcode::RRHL | Opcode::SLAHL | Opcode::SRAHL | Opcode::SLLHL |
+            Opcode::SRLHL | Opcode::SETBHL(..) | Opcode::RESBHL(..) => 15,
+            Opcode::LDHLNN(..) | Opcode::LDNNHL(..) | Opcode::LDI | Opcode::LDIR | Opcode::LDD |
+            Opcode::LDDR | Opcode::CPI | Opcode::CPIR | Opcode::CPD | Opcode::CPDR |
+            Opcode::INI | Opcode::INIR | Opcode::IND | Opcode::INDR | Opcode::OUTI |
+            Opcode::OTIR | Opcode::OUTD | Opcode::OTDR => 16,
+            Opcode::CALLNN(..) => 17,
+            Opcode::RLD | Opcode::RRD => 18,
+            Opcode::LDRIXD(..) | Opcode::LDRIYD(..) | Opcode::LDIXDR(..) | Opcode::LDIYDR(..) |
+            Opcode::LDIXDN(..) | Opcode::LDIYDN(..) | Opcode::EXSPHL | Opcode::ADDAIXD(..) |
+            Opcode::ADDAIYD(..) | Opcode::ADCAIXD(..) | Opcode::ADCAIYD(..) |
+            Opcode::SUBAIXD(..) | Opcode::SUBAIYD(..) | Opcode::SBCAIXD(..) |
+            Opcode::SBCAIYD(..) | Opcode::ANDAIXD(..) | Opcode::ANDAIYD(..) |
+            Opcode::ORAIXD(..) | Opcode::ORAIYD(..) | Opcode::XORAIXD(..) |
+            Opcode::XORAIYD(..) | Opcode::CPAIXD(..) | Opcode::CPAIYD(..) => 19,
+            Opcode::LDDDNN2(..) | Opcode::LDIXNN2(..) | Opcode::LDIYNN2(..) |
+            Opcode::LDNNDD(..) | Opcode::LDNNIX(..) | Opcode::LDNNIY(..) | Opcode::BITBIXD(..) |
+            Opcode::BITBIYD(..) => 20,
+            Opcode::EXSPIX | Opcode::EXSPIY | Opcode::INCIXD(..) | Opcode::INCIYD(..) |
+            Opcode::DECIXD(..) | Opcode::DECIYD(..) | Opcode::RLCIXD(..) | Opcode::RLCIYD(..) |
+            Opcode::RRCIXD(..) | Opcode::RRCIYD(..) | Opcode::RLIXD(..) | Opcode::RLIYD(..) |
+            Opcode::RRIXD(..) | Opcode::RRIYD(..) | Opcode::SLAIXD(..) | Opcode::SLAIYD(..) |
+            Opcode::SRAIXD(..) | Opcode::SRAIYD(..) | Opcode::SLLIXD(..) | Opcode::SLLIYD(..) |
+            Opcode::SRLIXD(..) | Opcode::SRLIYD(..) | Opcode::SETBIXD(..) |
+            Opcode::SETBIYD(..) | Opcode::RESBIXD(..) | Opcode::RESBIYD(..) |
+            Opcode::RLCIXDR(..) | Opcode::RLCIYDR(..) | Opcode::RRCIXDR(..) |
+            Opcode::RRCIYDR(..) | Opcode::RLIXDR(..) | Opcode::RLIYDR(..) | Opcode::RRIXDR(..) |
+            Opcode::RRIYDR(..) | Opcode::SLAIXDR(..) | Opcode::SLAIYDR(..) |
+            Opcode::SRAIXDR(..) | Opcode::SRAIYDR(..) | Opcode::SLLIXDR(..) |
+            Opcode::SLLIYDR(..) | Opcode::SRLIXDR(..) | Opcode::SRLIYDR(..) |
+            Opcode::SETBIXDR(..) | Opcode::SETBIYDR(..) | Opcode::RESBIXDR(..) |
+            Opcode::RESBIYDR(..) => 23,
+        }
+    }
+}
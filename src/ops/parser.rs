@@ -1,6 +1,9 @@
 use ops::opcodes::Reg;
 use ops::opcodes::BigReg;
 use ops::opcodes::Opcode;
+use ops::opcodes::Displacement;
+use ops::opcodes::Bit;
+use ops::opcodes::Condition;
 
 fn bits_to_reg(bit1: u8, bit2: u8, bit3: u8) -> Reg {
     match (bit1, bit2, bit3) {
@@ -25,6 +28,42 @@ fn bits_to_bigreg1(bit1: u8, bit2: u8) -> BigReg {
     }
 }
 
+fn bits_to_bigreg2(bit1: u8, bit2: u8) -> BigReg {
+    match (bit1, bit2) {
+        (0,0) => BigReg::BC,
+        (0,1) => BigReg::DE,
+        (1,0) => BigReg::HL,
+        (1,1) => BigReg::AF,
+        _ => unreachable!()
+    }
+}
+
+fn bits_to_index_bigreg(bit1: u8, bit2: u8, index: BigReg) -> BigReg {
+    // ADD IX,pp and ADD IY,rr name the index register where HL would be
+    match bits_to_bigreg1(bit1, bit2) {
+        BigReg::HL => index,
+        reg => reg,
+    }
+}
+
+fn bits_to_condition(bit1: u8, bit2: u8, bit3: u8) -> Condition {
+    match (bit1, bit2, bit3) {
+        (0,0,0) => Condition::NonZero,
+        (0,0,1) => Condition::Zero,
+        (0,1,0) => Condition::NoCarry,
+        (0,1,1) => Condition::Carry,
+        (1,0,0) => Condition::ParityOdd,
+        (1,0,1) => Condition::ParityEven,
+        (1,1,0) => Condition::PositiveSign,
+        (1,1,1) => Condition::NegativeSign,
+        _ => unreachable!()
+    }
+}
+
+fn bits_to_bit(bit1: u8, bit2: u8, bit3: u8) -> Bit {
+    (bit1 << 2) | (bit2 << 1) | bit3
+}
+
 fn byte_to_bits(byte: u8) -> (u8, u8, u8, u8, u8, u8, u8, u8) {
     (byte >> 7 & 1,
      byte >> 6 & 1,
@@ -36,62 +75,265 @@ fn byte_to_bits(byte: u8) -> (u8, u8, u8, u8, u8, u8, u8, u8) {
      byte & 1)
 }
 
+fn next_byte(code: &mut dyn Iterator<Item=u8>) -> u8 {
+    // A truncated stream reads as zeros instead of aborting the decode
+    code.next().unwrap_or(0)
+}
+
+fn next_u16(code: &mut dyn Iterator<Item=u8>) -> u16 {
+    let low = next_byte(code) as u16;
+    let high = next_byte(code) as u16;
+    (high << 8) + low
+}
 
-pub fn parse_op(code: &mut Iterator<Item=u8>) -> (u8, Opcode) {
-    let byte = code.next().unwrap();
+fn next_displacement(code: &mut dyn Iterator<Item=u8>) -> Displacement {
+    next_byte(code) as Displacement
+}
+
+fn parse_ddcb(displacement: Displacement, byte: u8) -> Opcode {
+    match byte_to_bits(byte) {
+        (0, 0, 0, 0, 0, 1, 1, 0) => Opcode::RLCIXD(displacement),
+        (0, 0, 0, 0, 1, 1, 1, 0) => Opcode::RRCIXD(displacement),
+        (0, 0, 0, 1, 0, 1, 1, 0) => Opcode::RLIXD(displacement),
+        (0, 0, 0, 1, 1, 1, 1, 0) => Opcode::RRIXD(displacement),
+        (0, 0, 1, 0, 0, 1, 1, 0) => Opcode::SLAIXD(displacement),
+        (0, 0, 1, 0, 1, 1, 1, 0) => Opcode::SRAIXD(displacement),
+        (0, 0, 1, 1, 0, 1, 1, 0) => Opcode::SLLIXD(displacement),
+        (0, 0, 1, 1, 1, 1, 1, 0) => Opcode::SRLIXD(displacement),
+        (0, 0, 0, 0, 0, r11, r12, r13) => {
+            Opcode::RLCIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 0, 1, r11, r12, r13) => {
+            Opcode::RRCIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 1, 0, r11, r12, r13) => {
+            Opcode::RLIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 1, 1, r11, r12, r13) => {
+            Opcode::RRIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 0, 0, r11, r12, r13) => {
+            Opcode::SLAIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 0, 1, r11, r12, r13) => {
+            Opcode::SRAIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 1, 0, r11, r12, r13) => {
+            Opcode::SLLIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 1, 1, r11, r12, r13) => {
+            Opcode::SRLIXDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        // Every BIT encoding tests memory, whatever register it names
+        (0, 1, b1, b2, b3, _, _, _) => Opcode::BITBIXD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 0, b1, b2, b3, 1, 1, 0) => Opcode::RESBIXD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 1, b1, b2, b3, 1, 1, 0) => Opcode::SETBIXD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 0, b1, b2, b3, r11, r12, r13) => {
+            Opcode::RESBIXDR(
+                bits_to_bit(b1, b2, b3),
+                displacement,
+                bits_to_reg(r11, r12, r13),
+            )
+        },
+        (1, 1, b1, b2, b3, r11, r12, r13) => {
+            Opcode::SETBIXDR(
+                bits_to_bit(b1, b2, b3),
+                displacement,
+                bits_to_reg(r11, r12, r13),
+            )
+        },
+        _ => unreachable!()
+    }
+}
+
+fn parse_fdcb(displacement: Displacement, byte: u8) -> Opcode {
+    match byte_to_bits(byte) {
+        (0, 0, 0, 0, 0, 1, 1, 0) => Opcode::RLCIYD(displacement),
+        (0, 0, 0, 0, 1, 1, 1, 0) => Opcode::RRCIYD(displacement),
+        (0, 0, 0, 1, 0, 1, 1, 0) => Opcode::RLIYD(displacement),
+        (0, 0, 0, 1, 1, 1, 1, 0) => Opcode::RRIYD(displacement),
+        (0, 0, 1, 0, 0, 1, 1, 0) => Opcode::SLAIYD(displacement),
+        (0, 0, 1, 0, 1, 1, 1, 0) => Opcode::SRAIYD(displacement),
+        (0, 0, 1, 1, 0, 1, 1, 0) => Opcode::SLLIYD(displacement),
+        (0, 0, 1, 1, 1, 1, 1, 0) => Opcode::SRLIYD(displacement),
+        (0, 0, 0, 0, 0, r11, r12, r13) => {
+            Opcode::RLCIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 0, 1, r11, r12, r13) => {
+            Opcode::RRCIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 1, 0, r11, r12, r13) => {
+            Opcode::RLIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 0, 1, 1, r11, r12, r13) => {
+            Opcode::RRIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 0, 0, r11, r12, r13) => {
+            Opcode::SLAIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 0, 1, r11, r12, r13) => {
+            Opcode::SRAIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 1, 0, r11, r12, r13) => {
+            Opcode::SLLIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        (0, 0, 1, 1, 1, r11, r12, r13) => {
+            Opcode::SRLIYDR(displacement, bits_to_reg(r11, r12, r13))
+        },
+        // Every BIT encoding tests memory, whatever register it names
+        (0, 1, b1, b2, b3, _, _, _) => Opcode::BITBIYD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 0, b1, b2, b3, 1, 1, 0) => Opcode::RESBIYD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 1, b1, b2, b3, 1, 1, 0) => Opcode::SETBIYD(bits_to_bit(b1, b2, b3), displacement),
+        (1, 0, b1, b2, b3, r11, r12, r13) => {
+            Opcode::RESBIYDR(
+                bits_to_bit(b1, b2, b3),
+                displacement,
+                bits_to_reg(r11, r12, r13),
+            )
+        },
+        (1, 1, b1, b2, b3, r11, r12, r13) => {
+            Opcode::SETBIYDR(
+                bits_to_bit(b1, b2, b3),
+                displacement,
+                bits_to_reg(r11, r12, r13),
+            )
+        },
+        _ => unreachable!()
+    }
+}
+
+pub fn parse_op(code: &mut dyn Iterator<Item=u8>) -> (u8, Opcode) {
+    let byte = next_byte(code);
     match byte {
+        0x00 => (1, Opcode::NOP),
         0x02 => (1, Opcode::LDBCA),
+        0x07 => (1, Opcode::RLCA),
         0x08 => (1, Opcode::EXAFAF2),
+        0x10 => (2, Opcode::DJNZE(next_displacement(code))),
+        0x18 => (2, Opcode::JRE(next_displacement(code))),
+        0x20 => (2, Opcode::JRNZE(next_displacement(code))),
+        0x28 => (2, Opcode::JRZE(next_displacement(code))),
+        0x30 => (2, Opcode::JRNCE(next_displacement(code))),
+        0x38 => (2, Opcode::JRCE(next_displacement(code))),
         0x0A => (1, Opcode::LDABC),
+        0x0F => (1, Opcode::RRCA),
         0x12 => (1, Opcode::LDDEA),
+        0x17 => (1, Opcode::RLA),
         0x1A => (1, Opcode::LDADE),
-        0x22 => {
-            let byte1 = (code.next().unwrap() as u16) << 8;
-            let byte2 = code.next().unwrap() as u16;
-            (3, Opcode::LDNNHL(byte1 + byte2))
-        }
-        0x2A => {
-            let byte1 = (code.next().unwrap() as u16) << 8;
-            let byte2 = code.next().unwrap() as u16;
-            (3, Opcode::LDHLNN(byte1 + byte2))
-        }
-        0x32 => {
-            let byte1 = (code.next().unwrap() as u16) << 8;
-            let byte2 = code.next().unwrap() as u16;
-            (3, Opcode::LDNNA(byte1 + byte2))
-        },
-        0x3A => {
-            let byte1 = (code.next().unwrap() as u16) << 8;
-            let byte2 = code.next().unwrap() as u16;
-            (3, Opcode::LDANN(byte1 + byte2))
-        },
+        0x1F => (1, Opcode::RRA),
+        0x22 => (3, Opcode::LDNNHL(next_u16(code))),
+        0x27 => (1, Opcode::DAA),
+        0x2F => (1, Opcode::CPL),
+        0x37 => (1, Opcode::SCF),
+        0x3F => (1, Opcode::CCF),
+        0x2A => (3, Opcode::LDHLNN(next_u16(code))),
+        0x32 => (3, Opcode::LDNNA(next_u16(code))),
+        0x3A => (3, Opcode::LDANN(next_u16(code))),
         0x34 => (1, Opcode::INCHL),
         0x35 => (1, Opcode::DECHL),
-        0x36 => (2, Opcode::LDHLN(code.next().unwrap())),
+        0x36 => (2, Opcode::LDHLN(next_byte(code))),
+        0x76 => (1, Opcode::HALT),
         0x86 => (1, Opcode::ADDAHL),
+        0x8E => (1, Opcode::ADCAHL),
         0x96 => (1, Opcode::SUBAHL),
         0x9E => (1, Opcode::SBCAHL),
         0xA6 => (1, Opcode::ANDAHL),
         0xAE => (1, Opcode::XORAHL),
         0xB6 => (1, Opcode::ORAHL),
         0xBE => (1, Opcode::CPAHL),
-        0xC6 => (2, Opcode::ADDAN(code.next().unwrap())),
-        0xD6 => (2, Opcode::SUBAN(code.next().unwrap())),
-        0xDE => (2, Opcode::SBCAN(code.next().unwrap())),
-        0xE6 => (2, Opcode::ANDAN(code.next().unwrap())),
-        0xEE => (2, Opcode::XORAN(code.next().unwrap())),
-        0xF6 => (2, Opcode::ORAN(code.next().unwrap())),
-        0xFE => (2, Opcode::CPAN(code.next().unwrap())),
+        0xC3 => (3, Opcode::JPNN(next_u16(code))),
+        0xC9 => (1, Opcode::RET),
+        0xCD => (3, Opcode::CALLNN(next_u16(code))),
+        0xC6 => (2, Opcode::ADDAN(next_byte(code))),
+        0xCE => (2, Opcode::ADCAN(next_byte(code))),
+        0xD6 => (2, Opcode::SUBAN(next_byte(code))),
+        0xDE => (2, Opcode::SBCAN(next_byte(code))),
+        0xE6 => (2, Opcode::ANDAN(next_byte(code))),
+        0xEE => (2, Opcode::XORAN(next_byte(code))),
+        0xF6 => (2, Opcode::ORAN(next_byte(code))),
+        0xFE => (2, Opcode::CPAN(next_byte(code))),
+        0xD3 => (2, Opcode::OUTNA(next_byte(code))),
         0xD9 => (1, Opcode::EXX),
+        0xDB => (2, Opcode::INAN(next_byte(code))),
         0xE3 => (1, Opcode::EXSPHL),
+        0xE9 => (1, Opcode::JPHL),
         0xEB => (1, Opcode::EXDEHL),
+        0xF3 => (1, Opcode::DI),
+        0xFB => (1, Opcode::EI),
+        0xCB => {
+            let second_byte = next_byte(code);
+            match byte_to_bits(second_byte) {
+                (0, 0, 0, 0, 0, 1, 1, 0) => (2, Opcode::RLCHL),
+                (0, 0, 0, 0, 1, 1, 1, 0) => (2, Opcode::RRCHL),
+                (0, 0, 0, 1, 0, 1, 1, 0) => (2, Opcode::RLHL),
+                (0, 0, 0, 1, 1, 1, 1, 0) => (2, Opcode::RRHL),
+                (0, 0, 1, 0, 0, 1, 1, 0) => (2, Opcode::SLAHL),
+                (0, 0, 1, 0, 1, 1, 1, 0) => (2, Opcode::SRAHL),
+                (0, 0, 1, 1, 0, 1, 1, 0) => (2, Opcode::SLLHL),
+                (0, 0, 1, 1, 1, 1, 1, 0) => (2, Opcode::SRLHL),
+                (0, 0, 0, 0, 0, r11, r12, r13) => {
+                    (2, Opcode::RLCR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 0, 0, 1, r11, r12, r13) => {
+                    (2, Opcode::RRCR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 0, 1, 0, r11, r12, r13) => {
+                    (2, Opcode::RLR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 0, 1, 1, r11, r12, r13) => {
+                    (2, Opcode::RRR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 1, 0, 0, r11, r12, r13) => {
+                    (2, Opcode::SLAR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 1, 0, 1, r11, r12, r13) => {
+                    (2, Opcode::SRAR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 1, 1, 0, r11, r12, r13) => {
+                    (2, Opcode::SLLR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 0, 1, 1, 1, r11, r12, r13) => {
+                    (2, Opcode::SRLR(bits_to_reg(r11, r12, r13)))
+                },
+                (0, 1, b1, b2, b3, 1, 1, 0) => (2, Opcode::BITBHL(bits_to_bit(b1, b2, b3))),
+                (1, 0, b1, b2, b3, 1, 1, 0) => (2, Opcode::RESBHL(bits_to_bit(b1, b2, b3))),
+                (1, 1, b1, b2, b3, 1, 1, 0) => (2, Opcode::SETBHL(bits_to_bit(b1, b2, b3))),
+                (0, 1, b1, b2, b3, r11, r12, r13) => {
+                    (2, Opcode::BITBR(
+                        bits_to_bit(b1, b2, b3),
+                        bits_to_reg(r11, r12, r13),
+                    ))
+                },
+                (1, 0, b1, b2, b3, r11, r12, r13) => {
+                    (2, Opcode::RESBR(
+                        bits_to_bit(b1, b2, b3),
+                        bits_to_reg(r11, r12, r13),
+                    ))
+                },
+                (1, 1, b1, b2, b3, r11, r12, r13) => {
+                    (2, Opcode::SETBR(
+                        bits_to_bit(b1, b2, b3),
+                        bits_to_reg(r11, r12, r13),
+                    ))
+                },
+                _ => (2, Opcode::NOP)
+            }
+        },
         0xED => {
-            let second_byte = code.next().unwrap();
+            let second_byte = next_byte(code);
             match second_byte {
                 0x57 => (2, Opcode::LDAI),
                 0x5F => (2, Opcode::LDAR),
                 0x47 => (2, Opcode::LDIA),
                 0x4F => (2, Opcode::LDRA),
+                0x46 | 0x4E | 0x66 | 0x6E => (2, Opcode::IM0),
+                0x56 | 0x76 => (2, Opcode::IM1),
+                0x5E | 0x7E => (2, Opcode::IM2),
+                0x67 => (2, Opcode::RRD),
+                0x6F => (2, Opcode::RLD),
+                0x44 => (2, Opcode::NEG),
+                0x45 => (2, Opcode::RETN),
+                0x4D => (2, Opcode::RETI),
                 0xA0 => (2, Opcode::LDI),
                 0xA1 => (2, Opcode::CPI),
                 0xA8 => (2, Opcode::LDD),
@@ -100,133 +342,173 @@ pub fn parse_op(code: &mut Iterator<Item=u8>) -> (u8, Opcode) {
                 0xB1 => (2, Opcode::CPIR),
                 0xB8 => (2, Opcode::LDDR),
                 0xB9 => (2, Opcode::CPDR),
+                0xA2 => (2, Opcode::INI),
+                0xAA => (2, Opcode::IND),
+                0xB2 => (2, Opcode::INIR),
+                0xBA => (2, Opcode::INDR),
+                0xA3 => (2, Opcode::OUTI),
+                0xAB => (2, Opcode::OUTD),
+                0xB3 => (2, Opcode::OTIR),
+                0xBB => (2, Opcode::OTDR),
+                0x70 => (2, Opcode::INFC),
+                0x71 => (2, Opcode::OUTC0),
                 _ => match byte_to_bits(second_byte) {
+                    (0, 1, r11, r12, r13, 0, 0, 0) => {
+                        (2, Opcode::INRC(
+                            bits_to_reg(r11, r12, r13),
+                        ))
+                    },
+                    (0, 1, r11, r12, r13, 0, 0, 1) => {
+                        (2, Opcode::OUTCR(
+                            bits_to_reg(r11, r12, r13),
+                        ))
+                    },
                     (0, 1, d1, d2, 1, 0, 1, 1) => {
-                        let byte1 = (code.next().unwrap() as u16) << 8;
-                        let byte2 = code.next().unwrap() as u16;
                         (4, Opcode::LDDDNN2(
                             bits_to_bigreg1(d1, d2),
-                            byte1 + byte2,
+                            next_u16(code),
                         ))
                     },
                     (0, 1, d1, d2, 0, 0, 1, 1) => {
-                        let byte1 = (code.next().unwrap() as u16) << 8;
-                        let byte2 = code.next().unwrap() as u16;
                         (4, Opcode::LDNNDD(
-                            byte1 + byte2,
+                            next_u16(code),
                             bits_to_bigreg1(d1, d2),
                         ))
                     },
-                    _ => (0, Opcode::NOP)
+                    (0, 1, s1, s2, 1, 0, 1, 0) => {
+                        (2, Opcode::ADCHLSS(
+                            bits_to_bigreg1(s1, s2),
+                        ))
+                    },
+                    (0, 1, s1, s2, 0, 0, 1, 0) => {
+                        (2, Opcode::SBCHLSS(
+                            bits_to_bigreg1(s1, s2),
+                        ))
+                    },
+                    // The remaining ED x4 encodings all mirror NEG
+                    (0, 1, _, _, _, 1, 0, 0) => (2, Opcode::NEG),
+                    // The remaining ED x5 encodings all mirror RETN
+                    (0, 1, _, _, _, 1, 0, 1) => (2, Opcode::RETN),
+                    _ => (2, Opcode::NOP)
                 }
             }
         },
         0xDD => {
-            let second_byte = code.next().unwrap();
+            let second_byte = next_byte(code);
             match second_byte {
-                0x21 => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDIXNN(byte1 + byte2))
-                },
-                0x22 => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDNNIX(byte1 + byte2))
-                },
-                0x2A => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDIXNN2(byte1 + byte2))
+                0xCB => {
+                    // The displacement comes before the final opcode byte
+                    let displacement = next_displacement(code);
+                    (4, parse_ddcb(displacement, next_byte(code)))
                 },
+                0x21 => (4, Opcode::LDIXNN(next_u16(code))),
+                0x23 => (2, Opcode::INCIX),
+                0x2B => (2, Opcode::DECIX),
+                0x22 => (4, Opcode::LDNNIX(next_u16(code))),
+                0x2A => (4, Opcode::LDIXNN2(next_u16(code))),
                 0x36 => {
                     (4, Opcode::LDIXDN(
-                        code.next().unwrap(),
-                        code.next().unwrap(),
+                        next_displacement(code),
+                        next_byte(code),
                     ))
                 },
-                0x34 => (3, Opcode::INCIXD(code.next().unwrap())),
-                0x35 => (3, Opcode::DECIXD(code.next().unwrap())),
-                0x86 => (3, Opcode::ADDAIXD(code.next().unwrap())),
-                0x96 => (3, Opcode::SUBAIXD(code.next().unwrap())),
-                0x9E => (3, Opcode::SBCAIXD(code.next().unwrap())),
-                0xA6 => (3, Opcode::ANDAIXD(code.next().unwrap())),
-                0xAE => (3, Opcode::XORAIXD(code.next().unwrap())),
-                0xB6 => (3, Opcode::ORAIXD(code.next().unwrap())),
-                0xBE => (3, Opcode::CPAIXD(code.next().unwrap())),
+                0x34 => (3, Opcode::INCIXD(next_displacement(code))),
+                0x35 => (3, Opcode::DECIXD(next_displacement(code))),
+                0x86 => (3, Opcode::ADDAIXD(next_displacement(code))),
+                0x8E => (3, Opcode::ADCAIXD(next_displacement(code))),
+                0x96 => (3, Opcode::SUBAIXD(next_displacement(code))),
+                0x9E => (3, Opcode::SBCAIXD(next_displacement(code))),
+                0xA6 => (3, Opcode::ANDAIXD(next_displacement(code))),
+                0xAE => (3, Opcode::XORAIXD(next_displacement(code))),
+                0xB6 => (3, Opcode::ORAIXD(next_displacement(code))),
+                0xBE => (3, Opcode::CPAIXD(next_displacement(code))),
 
+                0x76 => (2, Opcode::HALT),
                 0xE1 => (2, Opcode::POPIX),
                 0xE3 => (2, Opcode::EXSPIX),
                 0xE5 => (2, Opcode::PUSHIX),
+                0xE9 => (2, Opcode::JPIX),
                 0xF9 => (2, Opcode::LDSPIX),
                 _ => match byte_to_bits(second_byte) {
+                    (0, 0, p1, p2, 1, 0, 0, 1) => {
+                        (2, Opcode::ADDIXPP(
+                            bits_to_index_bigreg(p1, p2, BigReg::IX),
+                        ))
+                    },
                     (0, 1, 1, 1, 0, r11, r12, r13) => {
                         (3, Opcode::LDIXDR(
-                            code.next().unwrap(),
+                            next_displacement(code),
                             bits_to_reg(r11, r12, r13),
                         ))
                     },
                     (0, 1, r11, r12, r13, 1, 1, 0) => {
                         (3, Opcode::LDRIXD(
                             bits_to_reg(r11, r12, r13),
-                            code.next().unwrap(),
+                            next_displacement(code),
                         ))
                     },
-                    _ => (0, Opcode::NOP)
+                    // An unused prefix behaves as a NOP and the next byte is
+                    // decoded as an instruction of its own
+                    _ => (1, Opcode::NOP)
                 }
             }
         },
         0xFD => {
-            let second_byte = code.next().unwrap();
+            let second_byte = next_byte(code);
             match second_byte {
-                0x21 => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDIYNN(byte1 + byte2))
-                },
-                0x22 => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDNNIY(byte1 + byte2))
-                },
-                0x2A => {
-                    let byte1 = (code.next().unwrap() as u16) << 8;
-                    let byte2 = code.next().unwrap() as u16;
-                    (4, Opcode::LDIYNN2(byte1 + byte2))
+                0xCB => {
+                    // The displacement comes before the final opcode byte
+                    let displacement = next_displacement(code);
+                    (4, parse_fdcb(displacement, next_byte(code)))
                 },
+                0x21 => (4, Opcode::LDIYNN(next_u16(code))),
+                0x23 => (2, Opcode::INCIY),
+                0x2B => (2, Opcode::DECIY),
+                0x22 => (4, Opcode::LDNNIY(next_u16(code))),
+                0x2A => (4, Opcode::LDIYNN2(next_u16(code))),
                 0x36 => {
                     (4, Opcode::LDIYDN(
-                        code.next().unwrap(),
-                        code.next().unwrap(),
+                        next_displacement(code),
+                        next_byte(code),
                     ))
                 },
-                0x34 => (3, Opcode::INCIYD(code.next().unwrap())),
-                0x35 => (3, Opcode::DECIYD(code.next().unwrap())),
-                0x86 => (3, Opcode::ADDAIYD(code.next().unwrap())),
-                0x96 => (3, Opcode::SUBAIYD(code.next().unwrap())),
-                0x9E => (3, Opcode::SBCAIYD(code.next().unwrap())),
-                0xA6 => (3, Opcode::ANDAIYD(code.next().unwrap())),
-                0xAE => (3, Opcode::XORAIYD(code.next().unwrap())),
-                0xB6 => (3, Opcode::ORAIYD(code.next().unwrap())),
-                0xBE => (3, Opcode::CPAIYD(code.next().unwrap())),
+                0x34 => (3, Opcode::INCIYD(next_displacement(code))),
+                0x35 => (3, Opcode::DECIYD(next_displacement(code))),
+                0x86 => (3, Opcode::ADDAIYD(next_displacement(code))),
+                0x8E => (3, Opcode::ADCAIYD(next_displacement(code))),
+                0x96 => (3, Opcode::SUBAIYD(next_displacement(code))),
+                0x9E => (3, Opcode::SBCAIYD(next_displacement(code))),
+                0xA6 => (3, Opcode::ANDAIYD(next_displacement(code))),
+                0xAE => (3, Opcode::XORAIYD(next_displacement(code))),
+                0xB6 => (3, Opcode::ORAIYD(next_displacement(code))),
+                0xBE => (3, Opcode::CPAIYD(next_displacement(code))),
+                0x76 => (2, Opcode::HALT),
                 0xE1 => (2, Opcode::POPIY),
                 0xE3 => (2, Opcode::EXSPIY),
                 0xE5 => (2, Opcode::PUSHIY),
+                0xE9 => (2, Opcode::JPIY),
                 0xF9 => (2, Opcode::LDSPIY),
                 _ => match byte_to_bits(second_byte) {
+                    (0, 0, p1, p2, 1, 0, 0, 1) => {
+                        (2, Opcode::ADDIYRR(
+                            bits_to_index_bigreg(p1, p2, BigReg::IY),
+                        ))
+                    },
                     (0, 1, 1, 1, 0, r11, r12, r13) => {
                         (3, Opcode::LDIYDR(
-                            code.next().unwrap(),
+                            next_displacement(code),
                             bits_to_reg(r11, r12, r13),
                         ))
                     },
                     (0, 1, r11, r12, r13, 1, 1, 0) => {
                         (3, Opcode::LDRIYD(
                             bits_to_reg(r11, r12, r13),
-                            code.next().unwrap(),
+                            next_displacement(code),
                         ))
                     },
-                    _ => (0, Opcode::NOP)
+                    // An unused prefix behaves as a NOP and the next byte is
+                    // decoded as an instruction of its own
+                    _ => (1, Opcode::NOP)
                 }
             }
         },
@@ -237,6 +519,11 @@ pub fn parse_op(code: &mut Iterator<Item=u8>) -> (u8, Opcode) {
                     bits_to_reg(r11, r12, r13),
                 ))
             },
+            (1, 0, 0, 0, 1, r11, r12, r13) => {
+                (1, Opcode::ADCAR(
+                    bits_to_reg(r11, r12, r13),
+                ))
+            },
             (1, 0, 0, 1, 0, r11, r12, r13) => {
                 (1, Opcode::SUBAR(
                     bits_to_reg(r11, r12, r13),
@@ -286,7 +573,7 @@ pub fn parse_op(code: &mut Iterator<Item=u8>) -> (u8, Opcode) {
             (0, 0, r11, r12, r13, 1, 1, 0) => {
                 (2, Opcode::LDRN(
                     bits_to_reg(r11, r12, r13),
-                    code.next().unwrap(),
+                    next_byte(code),
                 ))
             },
             (0, 0, r11, r12, r13, 1, 0, 0) => {
@@ -300,24 +587,55 @@ pub fn parse_op(code: &mut Iterator<Item=u8>) -> (u8, Opcode) {
                 ))
             },
             (0, 0, d1, d2, 0, 0, 0, 1) => {
-                let byte1 = (code.next().unwrap() as u16) << 8;
-                let byte2 = code.next().unwrap() as u16;
                 (3, Opcode::LDDDNN(
                     bits_to_bigreg1(d1, d2),
-                    byte1 + byte2,
+                    next_u16(code),
+                ))
+            },
+            (0, 0, s1, s2, 1, 0, 0, 1) => {
+                (1, Opcode::ADDHLSS(
+                    bits_to_bigreg1(s1, s2),
+                ))
+            },
+            (0, 0, s1, s2, 0, 0, 1, 1) => {
+                (1, Opcode::INCSS(
+                    bits_to_bigreg1(s1, s2),
+                ))
+            },
+            (0, 0, s1, s2, 1, 0, 1, 1) => {
+                (1, Opcode::DECSS(
+                    bits_to_bigreg1(s1, s2),
+                ))
+            },
+            (1, 1, c1, c2, c3, 0, 0, 0) => {
+                (1, Opcode::RETCC(
+                    bits_to_condition(c1, c2, c3),
+                ))
+            },
+            (1, 1, c1, c2, c3, 1, 0, 0) => {
+                (3, Opcode::CALLCCNN(
+                    bits_to_condition(c1, c2, c3),
+                    next_u16(code),
+                ))
+            },
+            (1, 1, _, _, _, 1, 1, 1) => (1, Opcode::RETP(byte & 0b00111000)),
+            (1, 1, c1, c2, c3, 0, 1, 0) => {
+                (3, Opcode::JPCCNN(
+                    bits_to_condition(c1, c2, c3),
+                    next_u16(code),
                 ))
             },
             (1, 1, d1, d2, 0, 1, 0, 1) => {
                 (1, Opcode::PUSHQQ(
-                    bits_to_bigreg1(d1, d2),
+                    bits_to_bigreg2(d1, d2),
                 ))
             },
             (1, 1, d1, d2, 0, 0, 0, 1) => {
                 (1, Opcode::POPQQ(
-                    bits_to_bigreg1(d1, d2),
+                    bits_to_bigreg2(d1, d2),
                 ))
             },
-            _ => (0, Opcode::NOP)
+            _ => (1, Opcode::NOP)
         }
     }
 }
@@ -4,6 +4,7 @@ use ops::parser::parse_op;
 use ops::opcodes::Reg;
 use ops::opcodes::BigReg;
 use ops::opcodes::Opcode;
+use ops::opcodes::Condition;
 
 macro_rules! assert_op {
     ($data:expr, $size:expr, $op:pat) => {{
@@ -85,7 +86,7 @@ fn test_parse_ldade() {
 
 #[test]
 fn test_parse_ldann() {
-    assert_op!(vec![0b00111010, 0b00000001, 0b00000010], 3, Opcode::LDANN(258));
+    assert_op!(vec![0b00111010, 0b00000001, 0b00000010], 3, Opcode::LDANN(513));
 }
 
 #[test]
@@ -100,7 +101,7 @@ fn test_parse_lddea() {
 
 #[test]
 fn test_parse_ldnna() {
-    assert_op!(vec![0b00110010, 0b00000001, 0b00000010], 3, Opcode::LDNNA(258));
+    assert_op!(vec![0b00110010, 0b00000001, 0b00000010], 3, Opcode::LDNNA(513));
 }
 
 #[test]
@@ -125,57 +126,57 @@ fn test_parse_ldra() {
 
 #[test]
 fn test_parse_ldddnn() {
-    assert_op!(vec![0b00000001, 0b00000001, 0b00000010], 3, Opcode::LDDDNN(BigReg::BC, 258));
+    assert_op!(vec![0b00000001, 0b00000001, 0b00000010], 3, Opcode::LDDDNN(BigReg::BC, 513));
 }
 
 #[test]
 fn test_parse_ldixnn() {
-    assert_op!(vec![0xDD, 0x21, 1, 2], 4, Opcode::LDIXNN(258));
+    assert_op!(vec![0xDD, 0x21, 1, 2], 4, Opcode::LDIXNN(513));
 }
 
 #[test]
 fn test_parse_ldiynn() {
-    assert_op!(vec![0xFD, 0x21, 1, 2], 4, Opcode::LDIYNN(258));
+    assert_op!(vec![0xFD, 0x21, 1, 2], 4, Opcode::LDIYNN(513));
 }
 
 #[test]
 fn test_parse_ldhlnn() {
-    assert_op!(vec![0x2A, 1, 2], 3, Opcode::LDHLNN(258));
+    assert_op!(vec![0x2A, 1, 2], 3, Opcode::LDHLNN(513));
 }
 
 #[test]
 fn test_parse_ldddnn2() {
-    assert_op!(vec![0xED, 0b01001011, 1, 2], 4, Opcode::LDDDNN2(BigReg::BC, 258));
+    assert_op!(vec![0xED, 0b01001011, 1, 2], 4, Opcode::LDDDNN2(BigReg::BC, 513));
 }
 
 #[test]
 fn test_parse_ldixnn2() {
-    assert_op!(vec![0xDD, 0x2A, 1, 2], 4, Opcode::LDIXNN2(258));
+    assert_op!(vec![0xDD, 0x2A, 1, 2], 4, Opcode::LDIXNN2(513));
 }
 
 #[test]
 fn test_parse_ldiynn2() {
-    assert_op!(vec![0xFD, 0x2A, 1, 2], 4, Opcode::LDIYNN2(258));
+    assert_op!(vec![0xFD, 0x2A, 1, 2], 4, Opcode::LDIYNN2(513));
 }
 
 #[test]
 fn test_parse_ldnnhl() {
-    assert_op!(vec![0x22, 1, 2], 3, Opcode::LDNNHL(258));
+    assert_op!(vec![0x22, 1, 2], 3, Opcode::LDNNHL(513));
 }
 
 #[test]
 fn test_parse_ldnndd() {
-    assert_op!(vec![0xED, 0b01000011, 1, 2], 4, Opcode::LDNNDD(258, BigReg::BC));
+    assert_op!(vec![0xED, 0b01000011, 1, 2], 4, Opcode::LDNNDD(513, BigReg::BC));
 }
 
 #[test]
 fn test_parse_ldnnix() {
-    assert_op!(vec![0xDD, 0x22, 1, 2], 4, Opcode::LDNNIX(258));
+    assert_op!(vec![0xDD, 0x22, 1, 2], 4, Opcode::LDNNIX(513));
 }
 
 #[test]
 fn test_parse_ldnniy() {
-    assert_op!(vec![0xFD, 0x22, 1, 2], 4, Opcode::LDNNIY(258));
+    assert_op!(vec![0xFD, 0x22, 1, 2], 4, Opcode::LDNNIY(513));
 }
 
 #[test]
@@ -318,6 +319,31 @@ fn test_parse_addaiyd() {
     assert_op!(vec![0xFD, 0x86, 1], 3, Opcode::ADDAIYD(1));
 }
 
+#[test]
+fn test_parse_adcar() {
+    assert_op!(vec![0b10001111], 1, Opcode::ADCAR(Reg::A));
+}
+
+#[test]
+fn test_parse_adcan() {
+    assert_op!(vec![0xCE, 1], 2, Opcode::ADCAN(1));
+}
+
+#[test]
+fn test_parse_adcahl() {
+    assert_op!(vec![0x8E], 1, Opcode::ADCAHL);
+}
+
+#[test]
+fn test_parse_adcaixd() {
+    assert_op!(vec![0xDD, 0x8E, 1], 3, Opcode::ADCAIXD(1));
+}
+
+#[test]
+fn test_parse_adcaiyd() {
+    assert_op!(vec![0xFD, 0x8E, 1], 3, Opcode::ADCAIYD(1));
+}
+
 #[test]
 fn test_parse_subar() {
     assert_op!(vec![0b10010111], 1, Opcode::SUBAR(Reg::A));
@@ -507,3 +533,510 @@ fn test_parse_decixd() {
 fn test_parse_deciyd() {
     assert_op!(vec![0xFD, 0x35, 1], 3, Opcode::DECIYD(1));
 }
+
+#[test]
+fn test_parse_inan() {
+    assert_op!(vec![0xDB, 1], 2, Opcode::INAN(1));
+}
+
+#[test]
+fn test_parse_inrc() {
+    assert_op!(vec![0xED, 0b01000000], 2, Opcode::INRC(Reg::B));
+}
+
+#[test]
+fn test_parse_infc() {
+    assert_op!(vec![0xED, 0x70], 2, Opcode::INFC);
+}
+
+#[test]
+fn test_parse_outna() {
+    assert_op!(vec![0xD3, 1], 2, Opcode::OUTNA(1));
+}
+
+#[test]
+fn test_parse_outcr() {
+    assert_op!(vec![0xED, 0b01111001], 2, Opcode::OUTCR(Reg::A));
+}
+
+#[test]
+fn test_parse_outc0() {
+    assert_op!(vec![0xED, 0x71], 2, Opcode::OUTC0);
+}
+
+#[test]
+fn test_parse_ini() {
+    assert_op!(vec![0xED, 0xA2], 2, Opcode::INI);
+}
+
+#[test]
+fn test_parse_inir() {
+    assert_op!(vec![0xED, 0xB2], 2, Opcode::INIR);
+}
+
+#[test]
+fn test_parse_ind() {
+    assert_op!(vec![0xED, 0xAA], 2, Opcode::IND);
+}
+
+#[test]
+fn test_parse_indr() {
+    assert_op!(vec![0xED, 0xBA], 2, Opcode::INDR);
+}
+
+#[test]
+fn test_parse_outi() {
+    assert_op!(vec![0xED, 0xA3], 2, Opcode::OUTI);
+}
+
+#[test]
+fn test_parse_otir() {
+    assert_op!(vec![0xED, 0xB3], 2, Opcode::OTIR);
+}
+
+#[test]
+fn test_parse_outd() {
+    assert_op!(vec![0xED, 0xAB], 2, Opcode::OUTD);
+}
+
+#[test]
+fn test_parse_otdr() {
+    assert_op!(vec![0xED, 0xBB], 2, Opcode::OTDR);
+}
+
+#[test]
+fn test_parse_negative_displacement() {
+    assert_op!(vec![0xDD, 0x7E, 0xFE], 3, Opcode::LDRIXD(Reg::A, -2));
+    assert_op!(vec![0xFD, 0x36, 0x80, 0xFF], 4, Opcode::LDIYDN(-128, 0xFF));
+}
+
+#[test]
+fn test_parse_never_panics() {
+    assert_eq!(parse_op(&mut vec![].into_iter()), (1, Opcode::NOP));
+    for first in 0..256 {
+        parse_op(&mut vec![first as u8].into_iter());
+        for second in 0..256 {
+            let (bytes, _) = parse_op(&mut vec![first as u8, second as u8, 0xFF, 0xFF].into_iter());
+            assert!((1..=4).contains(&bytes));
+        }
+    }
+}
+
+#[test]
+fn test_parse_rlcr() {
+    assert_op!(vec![0xCB, 0x00], 2, Opcode::RLCR(Reg::B));
+}
+
+#[test]
+fn test_parse_rlchl() {
+    assert_op!(vec![0xCB, 0x06], 2, Opcode::RLCHL);
+}
+
+#[test]
+fn test_parse_rrcr() {
+    assert_op!(vec![0xCB, 0x09], 2, Opcode::RRCR(Reg::C));
+}
+
+#[test]
+fn test_parse_rrchl() {
+    assert_op!(vec![0xCB, 0x0E], 2, Opcode::RRCHL);
+}
+
+#[test]
+fn test_parse_rlr() {
+    assert_op!(vec![0xCB, 0x12], 2, Opcode::RLR(Reg::D));
+}
+
+#[test]
+fn test_parse_rlhl() {
+    assert_op!(vec![0xCB, 0x16], 2, Opcode::RLHL);
+}
+
+#[test]
+fn test_parse_rrr() {
+    assert_op!(vec![0xCB, 0x1B], 2, Opcode::RRR(Reg::E));
+}
+
+#[test]
+fn test_parse_rrhl() {
+    assert_op!(vec![0xCB, 0x1E], 2, Opcode::RRHL);
+}
+
+#[test]
+fn test_parse_slar() {
+    assert_op!(vec![0xCB, 0x24], 2, Opcode::SLAR(Reg::H));
+}
+
+#[test]
+fn test_parse_slahl() {
+    assert_op!(vec![0xCB, 0x26], 2, Opcode::SLAHL);
+}
+
+#[test]
+fn test_parse_srar() {
+    assert_op!(vec![0xCB, 0x2D], 2, Opcode::SRAR(Reg::L));
+}
+
+#[test]
+fn test_parse_srahl() {
+    assert_op!(vec![0xCB, 0x2E], 2, Opcode::SRAHL);
+}
+
+#[test]
+fn test_parse_sllr() {
+    assert_op!(vec![0xCB, 0x37], 2, Opcode::SLLR(Reg::A));
+}
+
+#[test]
+fn test_parse_sllhl() {
+    assert_op!(vec![0xCB, 0x36], 2, Opcode::SLLHL);
+}
+
+#[test]
+fn test_parse_srlr() {
+    assert_op!(vec![0xCB, 0x38], 2, Opcode::SRLR(Reg::B));
+}
+
+#[test]
+fn test_parse_srlhl() {
+    assert_op!(vec![0xCB, 0x3E], 2, Opcode::SRLHL);
+}
+
+#[test]
+fn test_parse_bitbr() {
+    assert_op!(vec![0xCB, 0b01101010], 2, Opcode::BITBR(5, Reg::D));
+}
+
+#[test]
+fn test_parse_bitbhl() {
+    assert_op!(vec![0xCB, 0b01110110], 2, Opcode::BITBHL(6));
+}
+
+#[test]
+fn test_parse_setbr() {
+    assert_op!(vec![0xCB, 0b11000111], 2, Opcode::SETBR(0, Reg::A));
+}
+
+#[test]
+fn test_parse_setbhl() {
+    assert_op!(vec![0xCB, 0b11111110], 2, Opcode::SETBHL(7));
+}
+
+#[test]
+fn test_parse_resbr() {
+    assert_op!(vec![0xCB, 0b10011101], 2, Opcode::RESBR(3, Reg::L));
+}
+
+#[test]
+fn test_parse_resbhl() {
+    assert_op!(vec![0xCB, 0b10001110], 2, Opcode::RESBHL(1));
+}
+
+#[test]
+fn test_parse_rlcixd() {
+    assert_op!(vec![0xDD, 0xCB, 1, 0x06], 4, Opcode::RLCIXD(1));
+}
+
+#[test]
+fn test_parse_srliyd() {
+    assert_op!(vec![0xFD, 0xCB, 0xFE, 0x3E], 4, Opcode::SRLIYD(-2));
+}
+
+#[test]
+fn test_parse_rlcixdr() {
+    assert_op!(vec![0xDD, 0xCB, 1, 0x00], 4, Opcode::RLCIXDR(1, Reg::B));
+}
+
+#[test]
+fn test_parse_slliydr() {
+    assert_op!(vec![0xFD, 0xCB, 1, 0x37], 4, Opcode::SLLIYDR(1, Reg::A));
+}
+
+#[test]
+fn test_parse_bitbixd() {
+    assert_op!(vec![0xDD, 0xCB, 1, 0x46], 4, Opcode::BITBIXD(0, 1));
+    assert_op!(vec![0xDD, 0xCB, 1, 0x78], 4, Opcode::BITBIXD(7, 1));
+}
+
+#[test]
+fn test_parse_bitbiyd() {
+    assert_op!(vec![0xFD, 0xCB, 1, 0x5E], 4, Opcode::BITBIYD(3, 1));
+}
+
+#[test]
+fn test_parse_setbixd() {
+    assert_op!(vec![0xDD, 0xCB, 1, 0xFE], 4, Opcode::SETBIXD(7, 1));
+}
+
+#[test]
+fn test_parse_setbiydr() {
+    assert_op!(vec![0xFD, 0xCB, 1, 0xC3], 4, Opcode::SETBIYDR(0, 1, Reg::E));
+}
+
+#[test]
+fn test_parse_resbiyd() {
+    assert_op!(vec![0xFD, 0xCB, 1, 0x86], 4, Opcode::RESBIYD(0, 1));
+}
+
+#[test]
+fn test_parse_resbixdr() {
+    assert_op!(vec![0xDD, 0xCB, 1, 0xBD], 4, Opcode::RESBIXDR(7, 1, Reg::L));
+}
+
+#[test]
+fn test_parse_indexed_cb_always_four_bytes() {
+    for &prefix in &[0xDD, 0xFD] {
+        for byte in 0..=255u8 {
+            let (bytes, _) = parse_op(&mut vec![prefix, 0xCB, 1, byte].into_iter());
+            assert_eq!(bytes, 4);
+        }
+    }
+}
+
+#[test]
+fn test_parse_jpnn() {
+    assert_op!(vec![0xC3, 0x34, 0x12], 3, Opcode::JPNN(0x1234));
+}
+
+#[test]
+fn test_parse_jpccnn() {
+    assert_op!(vec![0xC2, 0x34, 0x12], 3, Opcode::JPCCNN(Condition::NonZero, 0x1234));
+    assert_op!(vec![0xFA, 0x34, 0x12], 3, Opcode::JPCCNN(Condition::NegativeSign, 0x1234));
+}
+
+#[test]
+fn test_parse_jre() {
+    assert_op!(vec![0x18, 0xFE], 2, Opcode::JRE(-2));
+}
+
+#[test]
+fn test_parse_jrce() {
+    assert_op!(vec![0x38, 0x05], 2, Opcode::JRCE(5));
+}
+
+#[test]
+fn test_parse_jrnce() {
+    assert_op!(vec![0x30, 0x05], 2, Opcode::JRNCE(5));
+}
+
+#[test]
+fn test_parse_jrze() {
+    assert_op!(vec![0x28, 0x05], 2, Opcode::JRZE(5));
+}
+
+#[test]
+fn test_parse_jrnze() {
+    assert_op!(vec![0x20, 0x05], 2, Opcode::JRNZE(5));
+}
+
+#[test]
+fn test_parse_jphl() {
+    assert_op!(vec![0xE9], 1, Opcode::JPHL);
+}
+
+#[test]
+fn test_parse_jpix() {
+    assert_op!(vec![0xDD, 0xE9], 2, Opcode::JPIX);
+}
+
+#[test]
+fn test_parse_jpiy() {
+    assert_op!(vec![0xFD, 0xE9], 2, Opcode::JPIY);
+}
+
+#[test]
+fn test_parse_djnze() {
+    assert_op!(vec![0x10, 0xFE], 2, Opcode::DJNZE(-2));
+}
+
+#[test]
+fn test_parse_callnn() {
+    assert_op!(vec![0xCD, 0x34, 0x12], 3, Opcode::CALLNN(0x1234));
+}
+
+#[test]
+fn test_parse_callccnn() {
+    assert_op!(vec![0xDC, 0x34, 0x12], 3, Opcode::CALLCCNN(Condition::Carry, 0x1234));
+}
+
+#[test]
+fn test_parse_ret() {
+    assert_op!(vec![0xC9], 1, Opcode::RET);
+}
+
+#[test]
+fn test_parse_retcc() {
+    assert_op!(vec![0xE8], 1, Opcode::RETCC(Condition::ParityEven));
+}
+
+#[test]
+fn test_parse_reti() {
+    assert_op!(vec![0xED, 0x4D], 2, Opcode::RETI);
+}
+
+#[test]
+fn test_parse_retn() {
+    assert_op!(vec![0xED, 0x45], 2, Opcode::RETN);
+    assert_op!(vec![0xED, 0x7D], 2, Opcode::RETN);
+}
+
+#[test]
+fn test_parse_retp() {
+    assert_op!(vec![0xC7], 1, Opcode::RETP(0x00));
+    assert_op!(vec![0xEF], 1, Opcode::RETP(0x28));
+    assert_op!(vec![0xFF], 1, Opcode::RETP(0x38));
+}
+
+#[test]
+fn test_parse_pushqq_af() {
+    assert_op!(vec![0xF5], 1, Opcode::PUSHQQ(BigReg::AF));
+}
+
+#[test]
+fn test_parse_popqq_af() {
+    assert_op!(vec![0xF1], 1, Opcode::POPQQ(BigReg::AF));
+}
+
+#[test]
+fn test_parse_addhlss() {
+    assert_op!(vec![0b00011001], 1, Opcode::ADDHLSS(BigReg::DE));
+}
+
+#[test]
+fn test_parse_adchlss() {
+    assert_op!(vec![0xED, 0b01111010], 2, Opcode::ADCHLSS(BigReg::SP));
+}
+
+#[test]
+fn test_parse_sbchlss() {
+    assert_op!(vec![0xED, 0b01000010], 2, Opcode::SBCHLSS(BigReg::BC));
+}
+
+#[test]
+fn test_parse_addixpp() {
+    assert_op!(vec![0xDD, 0b00001001], 2, Opcode::ADDIXPP(BigReg::BC));
+    assert_op!(vec![0xDD, 0b00101001], 2, Opcode::ADDIXPP(BigReg::IX));
+    assert_op!(vec![0xDD, 0b00111001], 2, Opcode::ADDIXPP(BigReg::SP));
+}
+
+#[test]
+fn test_parse_addiyrr() {
+    assert_op!(vec![0xFD, 0b00011001], 2, Opcode::ADDIYRR(BigReg::DE));
+    assert_op!(vec![0xFD, 0b00101001], 2, Opcode::ADDIYRR(BigReg::IY));
+}
+
+#[test]
+fn test_parse_incss() {
+    assert_op!(vec![0b00100011], 1, Opcode::INCSS(BigReg::HL));
+}
+
+#[test]
+fn test_parse_incix() {
+    assert_op!(vec![0xDD, 0x23], 2, Opcode::INCIX);
+}
+
+#[test]
+fn test_parse_inciy() {
+    assert_op!(vec![0xFD, 0x23], 2, Opcode::INCIY);
+}
+
+#[test]
+fn test_parse_decss() {
+    assert_op!(vec![0b00111011], 1, Opcode::DECSS(BigReg::SP));
+}
+
+#[test]
+fn test_parse_decix() {
+    assert_op!(vec![0xDD, 0x2B], 2, Opcode::DECIX);
+}
+
+#[test]
+fn test_parse_deciy() {
+    assert_op!(vec![0xFD, 0x2B], 2, Opcode::DECIY);
+}
+
+#[test]
+fn test_parse_daa() {
+    assert_op!(vec![0x27], 1, Opcode::DAA);
+}
+
+#[test]
+fn test_parse_cpl() {
+    assert_op!(vec![0x2F], 1, Opcode::CPL);
+}
+
+#[test]
+fn test_parse_neg() {
+    assert_op!(vec![0xED, 0x44], 2, Opcode::NEG);
+    assert_op!(vec![0xED, 0x7C], 2, Opcode::NEG);
+}
+
+#[test]
+fn test_parse_ccf() {
+    assert_op!(vec![0x3F], 1, Opcode::CCF);
+}
+
+#[test]
+fn test_parse_scf() {
+    assert_op!(vec![0x37], 1, Opcode::SCF);
+}
+
+#[test]
+fn test_parse_rlca() {
+    assert_op!(vec![0x07], 1, Opcode::RLCA);
+}
+
+#[test]
+fn test_parse_rla() {
+    assert_op!(vec![0x17], 1, Opcode::RLA);
+}
+
+#[test]
+fn test_parse_rrca() {
+    assert_op!(vec![0x0F], 1, Opcode::RRCA);
+}
+
+#[test]
+fn test_parse_rra() {
+    assert_op!(vec![0x1F], 1, Opcode::RRA);
+}
+
+#[test]
+fn test_parse_rld() {
+    assert_op!(vec![0xED, 0x6F], 2, Opcode::RLD);
+}
+
+#[test]
+fn test_parse_rrd() {
+    assert_op!(vec![0xED, 0x67], 2, Opcode::RRD);
+}
+
+#[test]
+fn test_parse_di() {
+    assert_op!(vec![0xF3], 1, Opcode::DI);
+}
+
+#[test]
+fn test_parse_ei() {
+    assert_op!(vec![0xFB], 1, Opcode::EI);
+}
+
+#[test]
+fn test_parse_im0() {
+    assert_op!(vec![0xED, 0x46], 2, Opcode::IM0);
+}
+
+#[test]
+fn test_parse_im1() {
+    assert_op!(vec![0xED, 0x56], 2, Opcode::IM1);
+}
+
+#[test]
+fn test_parse_im2() {
+    assert_op!(vec![0xED, 0x5E], 2, Opcode::IM2);
+}
+
+#[test]
+fn test_parse_halt() {
+    assert_op!(vec![0x76], 1, Opcode::HALT);
+}